@@ -44,18 +44,15 @@ fn main() {
         .write_header(&mut outfh)
         .unwrap();
 
-    loop {
-        match decoder.read_frame() {
-            Ok(frame) => {
-                resizer
-                    .resize(frame.get_y_plane().as_gray(), dst.as_gray_mut())
-                    .unwrap();
-                let out_frame = y4m::Frame::new([&dst, &[], &[]], None);
-                if encoder.write_frame(&out_frame).is_err() {
-                    break;
-                }
-            }
-            _ => break,
+    let mut frames = decoder.frames();
+    while let Some(frame) = frames.next() {
+        let frame = frame.unwrap();
+        resizer
+            .resize(frame.get_y_plane().as_gray(), dst.as_gray_mut())
+            .unwrap();
+        let out_frame = y4m::Frame::new([&dst, &[], &[]], None);
+        if encoder.write_frame(&out_frame).is_err() {
+            break;
         }
     }
 }
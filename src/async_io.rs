@@ -0,0 +1,272 @@
+//! Asynchronous decoder and encoder over `futures::io` traits.
+
+use futures_util::io::{
+    AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader,
+};
+use std::io;
+
+use crate::{
+    copy_until, parse_frame_header_line, parse_header_line, ChromaSiting, ColorDescription,
+    ColorRange, Colorspace, EncoderBuilder, Error, Frame, FrameBuf, FrameWriter, Header,
+    Interlacing, Limits, Plane, Ratio, MAX_PARAMS_SIZE, TERMINATOR,
+};
+
+// Async counterpart of `EnhancedRead::read_until_byte`.
+async fn read_until_byte<R: AsyncRead + Unpin>(
+    reader: &mut BufReader<R>,
+    ch: u8,
+    buf: &mut [u8],
+) -> Result<usize, Error> {
+    let mut collected = 0;
+    loop {
+        let available = match reader.fill_buf().await {
+            Ok(available) => available,
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(Error::IoError(err)),
+        };
+        let (consumed, found) = copy_until(available, ch, buf, &mut collected)?;
+        reader.consume_unpin(consumed);
+        if found {
+            return Ok(collected);
+        }
+    }
+}
+
+// Async counterpart of `read_frame_data`.
+async fn read_frame_data<R: AsyncRead + Unpin>(
+    reader: &mut BufReader<R>,
+    buf: &mut [u8],
+) -> Result<(), Error> {
+    let mut read = 0;
+    while read < buf.len() {
+        match reader.read(&mut buf[read..]).await {
+            Ok(0) => {
+                return Err(Error::TruncatedFrame {
+                    read,
+                    expected: buf.len(),
+                })
+            }
+            Ok(chunk_size) => read += chunk_size,
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(Error::IoError(err)),
+        }
+    }
+    Ok(())
+}
+
+/// Asynchronous YUV4MPEG2 decoder.
+///
+/// Same as `Decoder` but reads from `futures::io::AsyncRead`. Input is
+/// buffered internally.
+pub struct AsyncDecoder<R: AsyncRead + Unpin> {
+    reader: BufReader<R>,
+    params_buf: Vec<u8>,
+    // Allocated on first `read_frame` call.
+    frame_buf: Vec<u8>,
+    header: Header,
+    // Stream offset and index of the next frame.
+    offset: u64,
+    frame_index: usize,
+}
+
+impl<R: AsyncRead + Unpin> AsyncDecoder<R> {
+    /// Create a new decoder instance.
+    pub async fn new(reader: R) -> Result<AsyncDecoder<R>, Error> {
+        AsyncDecoder::new_with_limits(reader, Limits::default()).await
+    }
+
+    /// Create a new decoder instance with custom limits.
+    pub async fn new_with_limits(reader: R, limits: Limits) -> Result<AsyncDecoder<R>, Error> {
+        let mut reader = BufReader::new(reader);
+        let mut params_buf = vec![0; MAX_PARAMS_SIZE];
+        let line = read_until_byte(&mut reader, TERMINATOR, &mut params_buf).await;
+        let (header, end_params_pos) = parse_header_line(line, &params_buf, limits)?;
+        Ok(AsyncDecoder {
+            reader,
+            params_buf,
+            frame_buf: Vec::new(),
+            header,
+            offset: end_params_pos as u64 + 1,
+            frame_index: 0,
+        })
+    }
+
+    /// Iterate over frames. End of input is indicated by `Error::EOF`.
+    ///
+    /// `Error::EOF` is returned only if the stream ends at a frame boundary,
+    /// otherwise one of the truncation errors is returned.
+    pub async fn read_frame(&mut self) -> Result<Frame<'_>, Error> {
+        let raw_params = self.read_frame_header().await?;
+        if self.frame_buf.is_empty() {
+            self.frame_buf = vec![0; self.header.frame_size];
+        }
+        read_frame_data(&mut self.reader, &mut self.frame_buf).await?;
+        self.finish_frame();
+        Ok(Frame::new_with_alpha(
+            self.header.split_planes(&self.frame_buf),
+            raw_params,
+        ))
+    }
+
+    /// Read next frame into the caller-provided buffer, reusing its
+    /// allocation. End of input is indicated by `Error::EOF`.
+    pub async fn read_frame_buf(&mut self, frame: &mut FrameBuf) -> Result<(), Error> {
+        let raw_params = self.read_frame_header().await?;
+        frame.reset(self.header.plane_lens, raw_params);
+        read_frame_data(&mut self.reader, &mut frame.data).await?;
+        self.finish_frame();
+        Ok(())
+    }
+
+    /// Read next frame directly into the caller-provided buffer and return
+    /// frame raw parameters if any. See `Decoder::read_frame_into`.
+    pub async fn read_frame_into(&mut self, buf: &mut [u8]) -> Result<Option<Vec<u8>>, Error> {
+        if buf.len() != self.header.frame_size {
+            return Err(Error::BadInput);
+        }
+        let raw_params = self.read_frame_header().await?;
+        read_frame_data(&mut self.reader, buf).await?;
+        self.finish_frame();
+        Ok(raw_params)
+    }
+
+    // Read frame header and return frame raw params.
+    async fn read_frame_header(&mut self) -> Result<Option<Vec<u8>>, Error> {
+        let line = read_until_byte(&mut self.reader, TERMINATOR, &mut self.params_buf).await;
+        let (raw_params, end_params_pos) =
+            parse_frame_header_line(line, &self.params_buf, self.offset, self.frame_index)?;
+        self.offset += end_params_pos as u64 + 1;
+        Ok(raw_params)
+    }
+
+    // Account frame data once it's successfully read.
+    fn finish_frame(&mut self) {
+        self.offset += self.header.frame_size as u64;
+        self.frame_index += 1;
+    }
+
+    header_getters!();
+}
+
+impl EncoderBuilder {
+    /// Write header to the asynchronous stream and create encoder instance.
+    pub async fn write_header_async<W: AsyncWrite + Unpin>(
+        self,
+        mut writer: W,
+    ) -> Result<AsyncEncoder<W>, Error> {
+        writer.write_all(&self.header_bytes()).await?;
+        Ok(AsyncEncoder {
+            writer,
+            frame_writer: self.frame_writer(),
+        })
+    }
+}
+
+/// Asynchronous YUV4MPEG2 encoder.
+///
+/// Same as `Encoder` but writes to `futures::io::AsyncWrite`. Created with
+/// `EncoderBuilder::write_header_async`.
+pub struct AsyncEncoder<W: AsyncWrite + Unpin> {
+    writer: W,
+    frame_writer: FrameWriter,
+}
+
+impl<W: AsyncWrite + Unpin> AsyncEncoder<W> {
+    /// Write next frame to the stream.
+    pub async fn write_frame(&mut self, frame: &Frame<'_>) -> Result<(), Error> {
+        let header = self.frame_writer.frame_header(frame)?;
+        self.writer.write_all(&header).await?;
+        for plane in frame.planes.iter() {
+            self.writer.write_all(plane).await?;
+        }
+        Ok(())
+    }
+
+    /// Write next frame made of the strided planes to the stream. See
+    /// `Encoder::write_planes`.
+    pub async fn write_planes(
+        &mut self,
+        planes: &[Plane<'_>],
+        raw_params: Option<&[u8]>,
+    ) -> Result<(), Error> {
+        let header = self.frame_writer.planes_header(planes, raw_params)?;
+        self.writer.write_all(&header).await?;
+        for row in planes.iter().flat_map(|plane| plane.rows()) {
+            self.writer.write_all(row).await?;
+        }
+        Ok(())
+    }
+
+    /// Flush the underlying writer.
+    pub async fn flush(&mut self) -> Result<(), Error> {
+        self.writer.flush().await.map_err(Error::IoError)
+    }
+
+    /// Return the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_executor::block_on;
+    use futures_util::io::Cursor;
+
+    #[test]
+    fn round_trip() {
+        block_on(async {
+            let mut encoder = EncoderBuilder::new(2, 2, Ratio::new(25, 1))
+                .write_header_async(Cursor::new(Vec::new()))
+                .await
+                .unwrap();
+            let frame = Frame::new([&[1; 4], &[2], &[3]], Some(b"Ixyz".to_vec()));
+            encoder.write_frame(&frame).await.unwrap();
+            let y = [4, 4, 0, 4, 4, 0];
+            let planes = [
+                Plane::new(&y, 2, 2, 3, 1).unwrap(),
+                Plane::new(&[5], 1, 1, 1, 1).unwrap(),
+                Plane::new(&[6], 1, 1, 1, 1).unwrap(),
+            ];
+            encoder.write_planes(&planes, None).await.unwrap();
+            encoder.flush().await.unwrap();
+            let data = encoder.into_inner().into_inner();
+            assert_eq!(
+                &data[..],
+                &b"YUV4MPEG2 W2 H2 F25:1 C420\nFRAME Ixyz\n\x01\x01\x01\x01\x02\x03\
+                   FRAME\n\x04\x04\x04\x04\x05\x06"[..]
+            );
+
+            let mut decoder = AsyncDecoder::new(Cursor::new(&data)).await.unwrap();
+            assert_eq!(decoder.header().get_width(), 2);
+            let frame = decoder.read_frame().await.unwrap();
+            assert_eq!(frame.get_y_plane(), [1; 4]);
+            assert_eq!(frame.get_raw_params(), Some(&b"Ixyz"[..]));
+            let mut frame = FrameBuf::new();
+            decoder.read_frame_buf(&mut frame).await.unwrap();
+            assert_eq!(frame.as_frame().get_v_plane(), [6]);
+            assert!(matches!(decoder.read_frame().await, Err(Error::EOF)));
+        });
+    }
+
+    #[test]
+    fn truncated_input() {
+        block_on(async {
+            let data = b"YUV4MPEG2 W2 H2 C420\nFRAME\n\x01\x01";
+            let mut decoder = AsyncDecoder::new(&data[..]).await.unwrap();
+            let mut buf = [0; 6];
+            assert!(matches!(
+                decoder.read_frame_into(&mut buf).await,
+                Err(Error::TruncatedFrame {
+                    read: 2,
+                    expected: 6
+                })
+            ));
+            assert!(matches!(
+                AsyncDecoder::new(&b"YUV4MPEG2 W2"[..]).await,
+                Err(Error::TruncatedHeader { read: 12 })
+            ));
+        });
+    }
+}
@@ -0,0 +1,485 @@
+//! Conversion of frames to interleaved RGB.
+//!
+//! ```
+//! # fn main() -> Result<(), y4m::Error> {
+//! # let data = b"YUV4MPEG2 W2 H2 F25:1 XCOLORMATRIX=BT709 C420\nFRAME\n\x10\x10\x10\x10\x80\x80";
+//! let mut decoder = y4m::SliceDecoder::new(data)?;
+//! // Matrix, color range and chroma siting are taken from the header.
+//! let converter = y4m::convert::Converter::from_header(decoder.header());
+//! let rgb = converter.to_rgb8(&decoder.read_frame()?)?;
+//! # assert_eq!(rgb, [0; 12]);
+//! # Ok(())
+//! # }
+//! ```
+
+use alloc::vec::Vec;
+
+use crate::{
+    get_plane_dimensions, ChromaSiting, ColorRange, Colorspace, Error, Frame, Header,
+    MatrixCoefficients, Plane,
+};
+
+/// Matrix used to convert YCbCr to RGB.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Matrix {
+    /// BT.601, used by SD video.
+    Bt601,
+    /// BT.709, used by HD video.
+    Bt709,
+    /// BT.2020 non-constant luminance, used by UHD video.
+    Bt2020,
+}
+
+impl Matrix {
+    /// Return the matrix for the given matrix coefficients if supported.
+    pub fn from_coefficients(matrix: MatrixCoefficients) -> Option<Matrix> {
+        match matrix {
+            MatrixCoefficients::Bt470bg | MatrixCoefficients::Smpte170m => Some(Matrix::Bt601),
+            MatrixCoefficients::Bt709 => Some(Matrix::Bt709),
+            MatrixCoefficients::Bt2020Ncl => Some(Matrix::Bt2020),
+            _ => None,
+        }
+    }
+
+    // Return Kr and Kb constants.
+    fn constants(self) -> (f32, f32) {
+        match self {
+            Matrix::Bt601 => (0.299, 0.114),
+            Matrix::Bt709 => (0.2126, 0.0722),
+            Matrix::Bt2020 => (0.2627, 0.0593),
+        }
+    }
+}
+
+/// Converter of frames to interleaved RGB.
+///
+/// Chroma planes are upsampled with bilinear interpolation according to the
+/// chroma siting.
+#[derive(Debug, Clone)]
+pub struct Converter {
+    width: usize,
+    height: usize,
+    colorspace: Colorspace,
+    matrix: Matrix,
+    color_range: ColorRange,
+    chroma_siting: ChromaSiting,
+}
+
+impl Converter {
+    /// Create a new converter for frames with the given dimensions and
+    /// colorspace.
+    ///
+    /// BT.601 matrix and limited range are used by default. Chroma siting is
+    /// implied by the colorspace, center siting is used if it's unspecified.
+    pub fn new(width: usize, height: usize, colorspace: Colorspace) -> Converter {
+        Converter {
+            width,
+            height,
+            colorspace,
+            matrix: Matrix::Bt601,
+            color_range: ColorRange::Limited,
+            chroma_siting: colorspace
+                .get_chroma_siting()
+                .unwrap_or(ChromaSiting::Center),
+        }
+    }
+
+    /// Create a new converter for frames of the stream with the given file
+    /// header. Color range, chroma siting and matrix are taken from the header
+    /// if specified.
+    pub fn from_header(header: &Header) -> Converter {
+        let mut converter = Converter::new(
+            header.get_width(),
+            header.get_height(),
+            header.get_colorspace(),
+        );
+        if let Some(color_range) = header.get_color_range() {
+            converter.color_range = color_range;
+        }
+        if let Some(chroma_siting) = header.get_chroma_siting() {
+            converter.chroma_siting = chroma_siting;
+        }
+        if let Some(matrix) = header
+            .get_color_description()
+            .matrix
+            .and_then(Matrix::from_coefficients)
+        {
+            converter.matrix = matrix;
+        }
+        converter
+    }
+
+    /// Specify conversion matrix.
+    pub fn with_matrix(mut self, matrix: Matrix) -> Self {
+        self.matrix = matrix;
+        self
+    }
+
+    /// Specify color range of the frames.
+    pub fn with_color_range(mut self, color_range: ColorRange) -> Self {
+        self.color_range = color_range;
+        self
+    }
+
+    /// Specify chroma siting of the frames. Only affects 4:2:0 colorspaces,
+    /// chroma of other subsampled colorspaces is co-sited with luma.
+    pub fn with_chroma_siting(mut self, chroma_siting: ChromaSiting) -> Self {
+        self.chroma_siting = chroma_siting;
+        self
+    }
+
+    /// Convert frame to 8-bit RGB.
+    ///
+    /// `Error::BadInput` is returned if frame planes don't match the
+    /// converter parameters.
+    pub fn to_rgb8(&self, frame: &Frame) -> Result<Vec<u8>, Error> {
+        let mut rgb = Vec::with_capacity(self.width * self.height * 3);
+        self.convert(frame, |[r, g, b, _]| {
+            rgb.extend_from_slice(&[
+                quantize(r, 255.0) as u8,
+                quantize(g, 255.0) as u8,
+                quantize(b, 255.0) as u8,
+            ]);
+        })?;
+        Ok(rgb)
+    }
+
+    /// Convert frame to 8-bit RGBA. Alpha is opaque unless colorspace has
+    /// alpha plane.
+    pub fn to_rgba8(&self, frame: &Frame) -> Result<Vec<u8>, Error> {
+        let mut rgba = Vec::with_capacity(self.width * self.height * 4);
+        self.convert(frame, |pixel| {
+            rgba.extend(pixel.iter().map(|&c| quantize(c, 255.0) as u8));
+        })?;
+        Ok(rgba)
+    }
+
+    /// Convert frame to 16-bit RGB, useful for high bit depth colorspaces.
+    pub fn to_rgb16(&self, frame: &Frame) -> Result<Vec<u16>, Error> {
+        let mut rgb = Vec::with_capacity(self.width * self.height * 3);
+        self.convert(frame, |[r, g, b, _]| {
+            rgb.extend_from_slice(&[
+                quantize(r, 65535.0) as u16,
+                quantize(g, 65535.0) as u16,
+                quantize(b, 65535.0) as u16,
+            ]);
+        })?;
+        Ok(rgb)
+    }
+
+    // Pass normalized RGBA of every pixel in raster order to the callback.
+    fn convert<F: FnMut([f32; 4])>(&self, frame: &Frame, mut put: F) -> Result<(), Error> {
+        let [y_plane, u_plane, v_plane, a_plane] =
+            frame.get_planes(self.width, self.height, self.colorspace)?;
+        let bit_depth = self.colorspace.get_bit_depth();
+        let max = ((1u32 << bit_depth) - 1) as f32;
+        let (y_offset, y_scale, c_scale) = match self.color_range {
+            ColorRange::Limited => {
+                let shift = bit_depth - 8;
+                (
+                    (16 << shift) as f32,
+                    (219 << shift) as f32,
+                    (224 << shift) as f32,
+                )
+            }
+            ColorRange::Full => (0.0, max, max),
+        };
+        let c_offset = (1u32 << (bit_depth - 1)) as f32;
+        let (kr, kb) = self.matrix.constants();
+        let kg = 1.0 - kr - kb;
+
+        let u = self.chroma_plane(&u_plane, 1);
+        let v = self.chroma_plane(&v_plane, 2);
+        let has_alpha = a_plane.width() > 0;
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let luma = (sample(&y_plane, x, y) - y_offset) / y_scale;
+                let (cb, cr) = match (&u, &v) {
+                    (Some(u), Some(v)) => (
+                        (u.interpolate(x, y) - c_offset) / c_scale,
+                        (v.interpolate(x, y) - c_offset) / c_scale,
+                    ),
+                    _ => (0.0, 0.0),
+                };
+                let r = luma + 2.0 * (1.0 - kr) * cr;
+                let b = luma + 2.0 * (1.0 - kb) * cb;
+                let g = (luma - kr * r - kb * b) / kg;
+                let a = if has_alpha {
+                    sample(&a_plane, x, y) / max
+                } else {
+                    1.0
+                };
+                put([r, g, b, a]);
+            }
+        }
+        Ok(())
+    }
+
+    fn chroma_plane<'a>(&self, plane: &'a Plane<'a>, index: usize) -> Option<ChromaPlane<'a>> {
+        // Chroma dimensions of the 4x2 block give the subsampling factors.
+        let (block_width, block_height) = get_plane_dimensions(4, 2, self.colorspace)[index];
+        if block_width == 0 || block_height == 0 {
+            return None;
+        }
+        let factors = (4 / block_width, 2 / block_height);
+        // Position of the first chroma sample relative to the first luma
+        // sample, in luma samples.
+        let offsets = match (factors, self.chroma_siting, index) {
+            ((2, 2), ChromaSiting::Center, _) => (0.5, 0.5),
+            ((2, 2), ChromaSiting::Left, _) => (0.0, 0.5),
+            // Cb is sited with the top luma row, Cr with the bottom one.
+            ((2, 2), ChromaSiting::PalDv, 2) => (0.0, 1.0),
+            _ => (0.0, 0.0),
+        };
+        Some(ChromaPlane {
+            plane,
+            columns: taps(self.width, plane.width(), factors.0, offsets.0),
+            rows: taps(self.height, plane.height(), factors.1, offsets.1),
+        })
+    }
+}
+
+// Chroma plane with precomputed interpolation taps for every luma column and
+// row.
+struct ChromaPlane<'a> {
+    plane: &'a Plane<'a>,
+    columns: Vec<Tap>,
+    rows: Vec<Tap>,
+}
+
+// Two neighbouring samples and weight of the second one.
+type Tap = (usize, usize, f32);
+
+impl ChromaPlane<'_> {
+    fn interpolate(&self, x: usize, y: usize) -> f32 {
+        let (x0, x1, wx) = self.columns[x];
+        let (y0, y1, wy) = self.rows[y];
+        let lerp = |a: f32, b: f32, w: f32| a + (b - a) * w;
+        let top = lerp(sample(self.plane, x0, y0), sample(self.plane, x1, y0), wx);
+        let bottom = lerp(sample(self.plane, x0, y1), sample(self.plane, x1, y1), wx);
+        lerp(top, bottom, wy)
+    }
+}
+
+// Compute interpolation taps of chroma samples for every luma position.
+fn taps(luma_len: usize, chroma_len: usize, factor: usize, offset: f32) -> Vec<Tap> {
+    (0..luma_len)
+        .map(|i| {
+            let pos = (i as f32 - offset) / factor as f32;
+            if pos <= 0.0 {
+                return (0, 0, 0.0);
+            }
+            let i0 = (pos as usize).min(chroma_len - 1);
+            let i1 = (i0 + 1).min(chroma_len - 1);
+            (i0, i1, (pos - i0 as f32).min(1.0))
+        })
+        .collect()
+}
+
+fn sample(plane: &Plane, x: usize, y: usize) -> f32 {
+    plane.get(x, y).unwrap_or(0) as f32
+}
+
+// Scale normalized value to the integer range, rounding to nearest.
+fn quantize(value: f32, max: f32) -> f32 {
+    value.clamp(0.0, 1.0) * max + 0.5
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FrameBuf;
+
+    fn assert_close(actual: &[u8], expected: &[u8]) {
+        assert_eq!(actual.len(), expected.len());
+        for (a, e) in actual.iter().zip(expected) {
+            assert!(
+                (*a as i32 - *e as i32).abs() <= 1,
+                "{:?} != {:?}",
+                actual,
+                expected
+            );
+        }
+    }
+
+    // Return one channel of the interleaved RGB8 pixels.
+    fn channel(rgb: &[u8], index: usize) -> Vec<u8> {
+        rgb.chunks(3).map(|pixel| pixel[index]).collect()
+    }
+
+    fn channel_u16(rgb: &[u16], index: usize) -> Vec<u16> {
+        rgb.chunks(3).map(|pixel| pixel[index]).collect()
+    }
+
+    #[test]
+    fn gray_limited_and_full_range() {
+        let frame = Frame::new([&[16, 235, 126, 255], &[128], &[128]], None);
+        let converter = Converter::new(2, 2, Colorspace::C420);
+        let rgb = converter.to_rgb8(&frame).unwrap();
+        assert_eq!(rgb, [0, 0, 0, 255, 255, 255, 128, 128, 128, 255, 255, 255]);
+        let rgb = converter
+            .with_color_range(ColorRange::Full)
+            .to_rgb8(&frame)
+            .unwrap();
+        assert_eq!(
+            rgb,
+            [16, 16, 16, 235, 235, 235, 126, 126, 126, 255, 255, 255]
+        );
+    }
+
+    #[test]
+    fn primaries_with_each_matrix() {
+        // Limited range red, green and blue for every matrix.
+        let cases = [
+            (
+                Matrix::Bt601,
+                [(81, 90, 240), (145, 54, 34), (41, 240, 110)],
+            ),
+            (
+                Matrix::Bt709,
+                [(63, 102, 240), (173, 42, 26), (32, 240, 118)],
+            ),
+            (
+                Matrix::Bt2020,
+                [(74, 97, 240), (164, 47, 25), (29, 240, 119)],
+            ),
+        ];
+        for &(matrix, ref colors) in cases.iter() {
+            let converter = Converter::new(1, 1, Colorspace::C444).with_matrix(matrix);
+            for (i, &(y, u, v)) in colors.iter().enumerate() {
+                let (y, u, v) = ([y], [u], [v]);
+                let frame = Frame::new([&y, &u, &v], None);
+                let mut expected = [0; 3];
+                expected[i] = 255;
+                assert_close(&converter.to_rgb8(&frame).unwrap(), &expected);
+            }
+        }
+    }
+
+    #[test]
+    fn high_bit_depth() {
+        let data = FrameBuf::from_u16([&[64, 940, 502], &[], &[]], None);
+        let converter = Converter::new(3, 1, Colorspace::Cmono10);
+        let rgb = converter.to_rgb16(&data.as_frame()).unwrap();
+        assert_eq!(channel_u16(&rgb, 0), [0, 65535, 32768]);
+        assert_eq!(
+            channel(&converter.to_rgb8(&data.as_frame()).unwrap(), 0),
+            [0, 255, 128]
+        );
+        let data = FrameBuf::from_u16([&[0, 1023, 512], &[], &[]], None);
+        let rgb = converter
+            .with_color_range(ColorRange::Full)
+            .to_rgb16(&data.as_frame())
+            .unwrap();
+        assert_eq!(channel_u16(&rgb, 0), [0, 65535, 32800]);
+    }
+
+    #[test]
+    fn alpha() {
+        let frame = Frame::new_with_alpha([&[235], &[128], &[128], &[100]], None);
+        let converter = Converter::new(1, 1, Colorspace::C444alpha);
+        assert_eq!(converter.to_rgba8(&frame).unwrap(), [255, 255, 255, 100]);
+        let frame = Frame::new([&[235], &[128], &[128]], None);
+        let converter = Converter::new(1, 1, Colorspace::C444);
+        assert_eq!(converter.to_rgba8(&frame).unwrap(), [255, 255, 255, 255]);
+    }
+
+    // Expected red and blue of limited range pixel with luma 126 and the
+    // given interpolated chroma, BT.601.
+    fn expected(cb: f32, cr: f32) -> (u8, u8) {
+        let y = (126.0 - 16.0) / 219.0;
+        let r = y + 1.402 * (cr - 128.0) / 224.0;
+        let b = y + 1.772 * (cb - 128.0) / 224.0;
+        ((r * 255.0 + 0.5) as u8, (b * 255.0 + 0.5) as u8)
+    }
+
+    // Convert 4x4 4:2:0 frame and return red of the first row and column and
+    // blue of the first column.
+    fn convert_420(
+        colorspace: Colorspace,
+        chroma_siting: ChromaSiting,
+        u: &[u8],
+        v: &[u8],
+    ) -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+        let frame = Frame::new([&[126; 16], u, v], None);
+        let rgb = Converter::new(4, 4, colorspace)
+            .with_chroma_siting(chroma_siting)
+            .to_rgb8(&frame)
+            .unwrap();
+        let row = channel(&rgb[..12], 0);
+        let column: Vec<_> = rgb.chunks(12).map(|row| &row[..3]).collect();
+        let red = column.iter().map(|pixel| pixel[0]).collect();
+        let blue = column.iter().map(|pixel| pixel[2]).collect();
+        (row, red, blue)
+    }
+
+    fn expected_red(cr: &[f32]) -> Vec<u8> {
+        cr.iter().map(|&cr| expected(128.0, cr).0).collect()
+    }
+
+    fn expected_blue(cb: &[f32]) -> Vec<u8> {
+        cb.iter().map(|&cb| expected(cb, 128.0).1).collect()
+    }
+
+    #[test]
+    fn center_siting() {
+        // Horizontal gradient.
+        let v = [100, 156, 100, 156];
+        let (row, _, _) = convert_420(Colorspace::C420, ChromaSiting::Center, &[128; 4], &v);
+        assert_close(&row, &expected_red(&[100.0, 114.0, 142.0, 156.0]));
+        // Vertical gradient.
+        let v = [100, 100, 156, 156];
+        let (_, red, _) = convert_420(Colorspace::C420, ChromaSiting::Center, &[128; 4], &v);
+        assert_close(&red, &expected_red(&[100.0, 114.0, 142.0, 156.0]));
+        // Implied by the colorspace.
+        let (_, red_jpeg, _) =
+            convert_420(Colorspace::C420jpeg, ChromaSiting::Center, &[128; 4], &v);
+        assert_eq!(red, red_jpeg);
+    }
+
+    #[test]
+    fn left_siting() {
+        let v = [100, 156, 100, 156];
+        let (row, _, _) = convert_420(Colorspace::C420mpeg2, ChromaSiting::Left, &[128; 4], &v);
+        assert_close(&row, &expected_red(&[100.0, 128.0, 156.0, 156.0]));
+        let v = [100, 100, 156, 156];
+        let (_, red, _) = convert_420(Colorspace::C420mpeg2, ChromaSiting::Left, &[128; 4], &v);
+        assert_close(&red, &expected_red(&[100.0, 114.0, 142.0, 156.0]));
+    }
+
+    #[test]
+    fn paldv_siting() {
+        // Cb is sited with the top luma row, Cr with the bottom one.
+        let gradient = [100, 100, 156, 156];
+        let (_, red, blue) = convert_420(
+            Colorspace::C420paldv,
+            ChromaSiting::PalDv,
+            &gradient,
+            &gradient,
+        );
+        assert_close(&blue, &expected_blue(&[100.0, 128.0, 156.0, 156.0]));
+        assert_close(&red, &expected_red(&[100.0, 100.0, 128.0, 156.0]));
+    }
+
+    #[test]
+    fn siting_ignored_without_vertical_subsampling() {
+        // 4:2:2 chroma is co-sited with luma.
+        let frame = Frame::new([&[126; 4], &[128; 2], &[100, 156]], None);
+        let rgb = Converter::new(4, 1, Colorspace::C422)
+            .with_chroma_siting(ChromaSiting::Center)
+            .to_rgb8(&frame)
+            .unwrap();
+        assert_close(
+            &channel(&rgb, 0),
+            &expected_red(&[100.0, 128.0, 156.0, 156.0]),
+        );
+    }
+
+    #[test]
+    fn mismatched_frame() {
+        let frame = Frame::new([&[0; 4], &[0], &[0]], None);
+        let converter = Converter::new(3, 3, Colorspace::C420);
+        assert!(matches!(converter.to_rgb8(&frame), Err(Error::BadInput)));
+    }
+}
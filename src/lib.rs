@@ -1,19 +1,50 @@
 //! # YUV4MPEG2 (.y4m) Encoder/Decoder
+//!
+//! Without the default `std` feature only the IO-free parts are available:
+//! header parsing and serialization, `get_plane_sizes`, `Parser`,
+//! `SliceDecoder`, `FrameWriter` and the `convert` module.
 #![deny(missing_docs)]
+#![cfg_attr(not(feature = "std"), no_std)]
 
-use std::fmt;
+extern crate alloc;
+
+use alloc::borrow::ToOwned;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::num;
+use core::ops::Range;
+use core::str;
+#[cfg(feature = "std")]
 use std::io;
+#[cfg(feature = "std")]
+use std::io::BufRead;
+#[cfg(feature = "std")]
+use std::io::BufReader;
+#[cfg(feature = "std")]
 use std::io::Read;
+#[cfg(feature = "std")]
+use std::io::Seek;
+#[cfg(feature = "std")]
+use std::io::SeekFrom;
+#[cfg(feature = "std")]
 use std::io::Write;
-use std::num;
-use std::str;
 
 const MAX_PARAMS_SIZE: usize = 1024;
 const FILE_MAGICK: &[u8] = b"YUV4MPEG2 ";
 const FRAME_MAGICK: &[u8] = b"FRAME";
+#[cfg(feature = "std")]
+const INDEX_MAGICK: &[u8] = b"Y4MINDEX2\n";
 const TERMINATOR: u8 = 0x0A;
 const FIELD_SEP: u8 = b' ';
 const RATIO_SEP: u8 = b':';
+const COLOR_RANGE_EXT: &[u8] = b"COLORRANGE=";
+const CHROMA_SITING_EXT: &[u8] = b"YSCSS=";
+const COLOR_PRIMARIES_EXT: &[u8] = b"COLORPRIMARIES=";
+const COLOR_TRC_EXT: &[u8] = b"COLORTRC=";
+const COLOR_MATRIX_EXT: &[u8] = b"COLORMATRIX=";
 
 /// Both encoding and decoding errors.
 #[derive(Debug)]
@@ -23,26 +54,52 @@ pub enum Error {
     EOF,
     /// Bad input parameters provided.
     BadInput,
-    /// Unknown colorspace (possibly just unimplemented).
+    /// Unknown colorspace (possibly just unimplemented). No longer returned.
+    #[deprecated(
+        note = "header parsing returns `ParseError` of `ParseErrorKind::UnknownColorspace` kind instead"
+    )]
     UnknownColorspace,
     /// Error while parsing the file/frame header.
-    // TODO(Kagami): Better granularity of parse errors.
     ParseError(ParseError),
     /// Error while reading/writing the file.
+    #[cfg(feature = "std")]
     IoError(io::Error),
     /// Out of memory (limits exceeded).
     OutOfMemory,
+    /// Stream ended in the middle of the file header.
+    TruncatedHeader {
+        /// Number of header bytes read.
+        read: usize,
+    },
+    /// Stream ended in the middle of the frame header.
+    TruncatedFrameHeader {
+        /// Number of frame header bytes read.
+        read: usize,
+    },
+    /// Stream ended in the middle of the frame data.
+    TruncatedFrame {
+        /// Number of frame data bytes read.
+        read: usize,
+        /// Number of frame data bytes expected.
+        expected: usize,
+    },
 }
 
+#[cfg(feature = "std")]
 impl std::error::Error for crate::Error {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match *self {
             Error::EOF => None,
             Error::BadInput => None,
+            #[allow(deprecated)]
             Error::UnknownColorspace => None,
             Error::ParseError(ref err) => Some(err),
+            #[cfg(feature = "std")]
             Error::IoError(ref err) => Some(err),
             Error::OutOfMemory => None,
+            Error::TruncatedHeader { .. } => None,
+            Error::TruncatedFrameHeader { .. } => None,
+            Error::TruncatedFrame { .. } => None,
         }
     }
 }
@@ -52,16 +109,30 @@ impl fmt::Display for crate::Error {
         match *self {
             Error::EOF => write!(f, "End of file"),
             Error::BadInput => write!(f, "Bad input parameters provided"),
-            Error::UnknownColorspace => write!(f, "Bad input parameters provided"),
+            #[allow(deprecated)]
+            Error::UnknownColorspace => write!(f, "Unknown colorspace"),
             Error::ParseError(ref err) => err.fmt(f),
+            #[cfg(feature = "std")]
             Error::IoError(ref err) => err.fmt(f),
             Error::OutOfMemory => write!(f, "Out of memory (limits exceeded)"),
+            Error::TruncatedHeader { read } => {
+                write!(f, "Truncated file header ({} bytes read)", read)
+            }
+            Error::TruncatedFrameHeader { read } => {
+                write!(f, "Truncated frame header ({} bytes read)", read)
+            }
+            Error::TruncatedFrame { read, expected } => write!(
+                f,
+                "Truncated frame data ({} of {} bytes read)",
+                read, expected
+            ),
         }
     }
 }
 
-/// Granular ParseError Definiations
-pub enum ParseError {
+/// Kind of the parse error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseErrorKind {
     /// Error reading y4m header
     InvalidY4M,
     /// Error parsing int
@@ -70,90 +141,335 @@ pub enum ParseError {
     Utf8,
     /// General Parsing Error
     General,
+    /// Unknown colorspace (possibly just unimplemented)
+    UnknownColorspace,
+    /// Required tag is missing
+    MissingTag,
 }
 
-impl std::error::Error for crate::ParseError {
-    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
-        match *self {
-            ParseError::InvalidY4M => None,
-            ParseError::Int => None,
-            ParseError::Utf8 => None,
-            ParseError::General => None,
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseErrorKind::InvalidY4M => write!(f, "Error parsing y4m header"),
+            ParseErrorKind::Int => write!(f, "Error parsing Int"),
+            ParseErrorKind::Utf8 => write!(f, "Error parsing UTF8"),
+            ParseErrorKind::General => write!(f, "General parsing error"),
+            ParseErrorKind::UnknownColorspace => write!(f, "Unknown colorspace"),
+            ParseErrorKind::MissingTag => write!(f, "Missing tag"),
         }
     }
 }
 
-impl fmt::Display for ParseError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            ParseError::InvalidY4M => write!(f, "Error parsing y4m header"),
-            ParseError::Int => write!(f, "Error parsing Int"),
-            ParseError::Utf8 => write!(f, "Error parsing UTF8"),
-            ParseError::General => write!(f, "General parsing error"),
+/// Error while parsing the file/frame header, with the location in the
+/// stream and the offending tag if known.
+#[derive(Debug, Clone)]
+pub struct ParseError {
+    kind: ParseErrorKind,
+    offset: Option<u64>,
+    frame: Option<usize>,
+    tag: Option<(u8, Vec<u8>)>,
+}
+
+impl ParseError {
+    fn new(kind: ParseErrorKind) -> ParseError {
+        ParseError {
+            kind,
+            offset: None,
+            frame: None,
+            tag: None,
         }
     }
+
+    fn at(mut self, offset: u64) -> ParseError {
+        self.offset.get_or_insert(offset);
+        self
+    }
+
+    fn in_frame(mut self, frame: usize) -> ParseError {
+        self.frame.get_or_insert(frame);
+        self
+    }
+
+    fn with_tag(mut self, name: u8, value: &[u8]) -> ParseError {
+        self.tag.get_or_insert_with(|| (name, value.to_owned()));
+        self
+    }
+
+    /// Return the kind of the error.
+    #[inline]
+    pub fn kind(&self) -> ParseErrorKind {
+        self.kind
+    }
+    /// Return the byte offset in the stream where the error occurred (start
+    /// of the offending tag or header), if known.
+    #[inline]
+    pub fn offset(&self) -> Option<u64> {
+        self.offset
+    }
+    /// Return the index of the frame where the error occurred, `None` for
+    /// the file header.
+    #[inline]
+    pub fn frame(&self) -> Option<usize> {
+        self.frame
+    }
+    /// Return the name of the offending tag, if known.
+    #[inline]
+    pub fn tag(&self) -> Option<u8> {
+        self.tag.as_ref().map(|t| t.0)
+    }
+    /// Return the value of the offending tag, if known.
+    #[inline]
+    pub fn value(&self) -> Option<&[u8]> {
+        self.tag.as_ref().map(|t| &t.1[..])
+    }
 }
 
-impl fmt::Debug for ParseError {
+#[cfg(feature = "std")]
+impl std::error::Error for crate::ParseError {}
+
+impl fmt::Display for ParseError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            ParseError::InvalidY4M => write!(f, "Error parsing y4m header"),
-            ParseError::Int => write!(f, "Error parsing Int"),
-            ParseError::Utf8 => write!(f, "Error parsing UTF8"),
-            ParseError::General => write!(f, "General parsing error"),
+        match self.tag {
+            Some((name, _)) if self.kind == ParseErrorKind::MissingTag => {
+                write!(f, "Missing {} tag", char::from(name))?
+            }
+            Some((name, ref value)) => write!(
+                f,
+                "Bad {} value `{}`",
+                char::from(name),
+                String::from_utf8_lossy(value)
+            )?,
+            None => self.kind.fmt(f)?,
+        }
+        if let Some(frame) = self.frame {
+            write!(f, " in frame {}", frame)?;
+        }
+        if let Some(offset) = self.offset {
+            write!(f, " at offset {}", offset)?;
         }
+        if self.tag.is_some() && self.kind != ParseErrorKind::MissingTag {
+            write!(f, " ({})", self.kind)?;
+        }
+        Ok(())
     }
 }
 
 macro_rules! parse_error {
     ($p:expr) => {
-        return Err(Error::ParseError($p))
+        return Err(Error::ParseError(ParseError::new($p)))
+    };
+}
+
+// Getters shared by all decoders, delegating to `Header`. By default
+// `self.header` must be a `Header`, otherwise pass the path to it.
+macro_rules! header_getters {
+    () => {
+        header_getters!(header);
+    };
+    ($($field:ident).+) => {
+        /// Return the parsed file header.
+        #[inline]
+        pub fn header(&self) -> &Header {
+            &self.$($field).+
+        }
+        /// Return file width.
+        #[inline]
+        pub fn get_width(&self) -> usize {
+            self.header().get_width()
+        }
+        /// Return file height.
+        #[inline]
+        pub fn get_height(&self) -> usize {
+            self.header().get_height()
+        }
+        /// Return file framerate.
+        #[inline]
+        pub fn get_framerate(&self) -> Ratio {
+            self.header().get_framerate()
+        }
+        /// Return file pixel aspect.
+        #[inline]
+        pub fn get_pixel_aspect(&self) -> Ratio {
+            self.header().get_pixel_aspect()
+        }
+        /// Return file colorspace, see `Header::get_colorspace`.
+        #[inline]
+        pub fn get_colorspace(&self) -> Colorspace {
+            self.header().get_colorspace()
+        }
+        /// Return file interlacing mode.
+        #[inline]
+        pub fn get_interlacing(&self) -> Interlacing {
+            self.header().get_interlacing()
+        }
+        /// Return file color range if specified with `COLORRANGE` extension.
+        #[inline]
+        pub fn get_color_range(&self) -> Option<ColorRange> {
+            self.header().get_color_range()
+        }
+        /// Return file chroma siting, see `Header::get_chroma_siting`.
+        #[inline]
+        pub fn get_chroma_siting(&self) -> Option<ChromaSiting> {
+            self.header().get_chroma_siting()
+        }
+        /// Return file color description, see `Header::get_color_description`.
+        #[inline]
+        pub fn get_color_description(&self) -> ColorDescription {
+            self.header().get_color_description()
+        }
+        /// Return file raw parameters.
+        #[inline]
+        pub fn get_raw_params(&self) -> &[u8] {
+            self.header().get_raw_params()
+        }
+        /// Return the bit depth per sample
+        #[inline]
+        pub fn get_bit_depth(&self) -> usize {
+            self.header().get_bit_depth()
+        }
+        /// Return the number of bytes in a sample
+        #[inline]
+        pub fn get_bytes_per_sample(&self) -> usize {
+            self.header().get_bytes_per_sample()
+        }
+        /// Return the size in bytes of the frame data (all planes).
+        #[inline]
+        pub fn get_frame_size(&self) -> usize {
+            self.header().get_frame_size()
+        }
+    };
+}
+
+pub mod convert;
+
+mod parser;
+
+pub use parser::{Event, Events, Parser};
+
+#[cfg(feature = "mmap")]
+mod mmap;
+
+#[cfg(feature = "mmap")]
+pub use mmap::MmapDecoder;
+
+#[cfg(feature = "async")]
+mod async_io;
+
+#[cfg(feature = "async")]
+pub use async_io::{AsyncDecoder, AsyncEncoder};
+
+impl Error {
+    // Attach context to the parse error, other errors are returned as is.
+    fn with_parse_context<F: FnOnce(ParseError) -> ParseError>(self, f: F) -> Error {
+        match self {
+            Error::ParseError(err) => Error::ParseError(f(err)),
+            err => err,
+        }
     }
 }
 
+#[cfg(feature = "std")]
 impl From<io::Error> for Error {
+    // End of stream is detected explicitly by decoder, so `UnexpectedEof` from
+    // the underlying reader is a real error.
     fn from(err: io::Error) -> Error {
-        match err.kind() {
-            io::ErrorKind::UnexpectedEof => Error::EOF,
-            _ => Error::IoError(err),
-        }
+        Error::IoError(err)
     }
 }
 
 impl From<num::ParseIntError> for Error {
     fn from(_: num::ParseIntError) -> Error {
-        Error::ParseError(ParseError::Int)
+        Error::ParseError(ParseError::new(ParseErrorKind::Int))
     }
 }
 
 impl From<str::Utf8Error> for Error {
     fn from(_: str::Utf8Error) -> Error {
-        Error::ParseError(ParseError::Utf8)
+        Error::ParseError(ParseError::new(ParseErrorKind::Utf8))
     }
 }
 
+#[cfg(feature = "std")]
 trait EnhancedRead {
-    fn read_until(&mut self, ch: u8, buf: &mut [u8]) -> Result<usize, Error>;
+    fn read_until_byte(&mut self, ch: u8, buf: &mut [u8]) -> Result<usize, Error>;
+    fn read_full(&mut self, buf: &mut [u8]) -> Result<usize, Error>;
 }
 
-impl<R: Read> EnhancedRead for R {
-    // Current implementation does one `read` call per byte. This might be a
-    // bit slow for long headers but it simplifies things: we don't need to
-    // check whether start of the next frame is already read and so on.
-    fn read_until(&mut self, ch: u8, buf: &mut [u8]) -> Result<usize, Error> {
+#[cfg(feature = "std")]
+impl<R: BufRead> EnhancedRead for R {
+    // Scans the buffered data, so a header usually costs a single `read`
+    // call. Data past the terminator stays in the buffer for the next read.
+    //
+    // End of stream is reported as `TruncatedHeader` with the number of bytes
+    // read so far, callers decide whether that's a clean EOF.
+    fn read_until_byte(&mut self, ch: u8, buf: &mut [u8]) -> Result<usize, Error> {
         let mut collected = 0;
-        while collected < buf.len() {
-            let chunk_size = self.read(&mut buf[collected..=collected])?;
-            if chunk_size == 0 {
-                return Err(Error::EOF);
-            }
-            if buf[collected] == ch {
+        loop {
+            let available = match self.fill_buf() {
+                Ok(available) => available,
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(Error::IoError(err)),
+            };
+            let (consumed, found) = copy_until(available, ch, buf, &mut collected)?;
+            self.consume(consumed);
+            if found {
                 return Ok(collected);
             }
-            collected += chunk_size;
         }
-        parse_error!(ParseError::General)
     }
+
+    // Like `read_exact` but returns the number of bytes read before the end
+    // of stream.
+    fn read_full(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        let mut collected = 0;
+        while collected < buf.len() {
+            match self.read(&mut buf[collected..]) {
+                Ok(0) => break,
+                Ok(chunk_size) => collected += chunk_size,
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(Error::IoError(err)),
+            }
+        }
+        Ok(collected)
+    }
+}
+
+// Copy buffered bytes into `buf` until `ch` is found. Return the number of
+// bytes to consume and whether the terminator was found, `collected` is then
+// the line length.
+fn copy_until(
+    available: &[u8],
+    ch: u8,
+    buf: &mut [u8],
+    collected: &mut usize,
+) -> Result<(usize, bool), Error> {
+    if available.is_empty() {
+        return Err(Error::TruncatedHeader { read: *collected });
+    }
+    let len = available.len().min(buf.len() - *collected);
+    if let Some(pos) = available[..len].iter().position(|&b| b == ch) {
+        buf[*collected..*collected + pos].copy_from_slice(&available[..pos]);
+        *collected += pos;
+        return Ok((pos + 1, true));
+    }
+    buf[*collected..*collected + len].copy_from_slice(&available[..len]);
+    *collected += len;
+    if *collected == buf.len() {
+        parse_error!(ParseErrorKind::General)
+    }
+    Ok((len, false))
+}
+
+#[cfg(feature = "std")]
+fn read_frame_data<R: BufRead>(reader: &mut R, buf: &mut [u8]) -> Result<(), Error> {
+    let read = reader.read_full(buf)?;
+    if read < buf.len() {
+        return Err(Error::TruncatedFrame {
+            read,
+            expected: buf.len(),
+        });
+    }
+    Ok(())
 }
 
 fn parse_bytes(buf: &[u8]) -> Result<usize, Error> {
@@ -161,8 +477,8 @@ fn parse_bytes(buf: &[u8]) -> Result<usize, Error> {
     Ok(str::from_utf8(buf)?.parse()?)
 }
 
-/// A newtype wrapper around Vec<u8> to ensure validity as a vendor extension.
-#[derive(Debug, Clone)]
+/// A newtype wrapper around `Vec<u8>` to ensure validity as a vendor extension.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct VendorExtensionString(Vec<u8>);
 
 impl VendorExtensionString {
@@ -205,7 +521,7 @@ impl Ratio {
     pub fn parse(value: &[u8]) -> Result<Ratio, Error> {
         let parts: Vec<_> = value.splitn(2, |&b| b == RATIO_SEP).collect();
         if parts.len() != 2 {
-            parse_error!(ParseError::General)
+            parse_error!(ParseErrorKind::General)
         }
         let num = parse_bytes(parts[0])?;
         let den = parse_bytes(parts[1])?;
@@ -219,46 +535,399 @@ impl fmt::Display for Ratio {
     }
 }
 
+/// Interlacing mode of the stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interlacing {
+    /// Progressive (non-interlaced) frames.
+    Progressive,
+    /// Interlaced, top field first.
+    TopFieldFirst,
+    /// Interlaced, bottom field first.
+    BottomFieldFirst,
+    /// Mixed mode, interlacing is specified per frame.
+    Mixed,
+    /// Unknown interlacing (`?`).
+    Unknown,
+}
+
+impl Interlacing {
+    /// Parse an interlacing mode from the `I` tag value.
+    pub fn parse(value: &[u8]) -> Result<Interlacing, Error> {
+        match value {
+            b"p" => Ok(Interlacing::Progressive),
+            b"t" => Ok(Interlacing::TopFieldFirst),
+            b"b" => Ok(Interlacing::BottomFieldFirst),
+            b"m" => Ok(Interlacing::Mixed),
+            b"?" => Ok(Interlacing::Unknown),
+            _ => parse_error!(ParseErrorKind::General),
+        }
+    }
+}
+
+impl fmt::Display for Interlacing {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let value = match *self {
+            Interlacing::Progressive => "p",
+            Interlacing::TopFieldFirst => "t",
+            Interlacing::BottomFieldFirst => "b",
+            Interlacing::Mixed => "m",
+            Interlacing::Unknown => "?",
+        };
+        f.write_str(value)
+    }
+}
+
+/// Range of the YUV values, stored as `COLORRANGE` vendor extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorRange {
+    /// Limited "studio swing" range (e.g. 16-235 for 8-bit luma).
+    Limited,
+    /// Full range (e.g. 0-255 for 8-bit).
+    Full,
+}
+
+impl ColorRange {
+    /// Parse a color range from the `COLORRANGE` extension value.
+    pub fn parse(value: &[u8]) -> Result<ColorRange, Error> {
+        match value {
+            b"LIMITED" => Ok(ColorRange::Limited),
+            b"FULL" => Ok(ColorRange::Full),
+            _ => parse_error!(ParseErrorKind::General),
+        }
+    }
+}
+
+impl fmt::Display for ColorRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let value = match *self {
+            ColorRange::Limited => "LIMITED",
+            ColorRange::Full => "FULL",
+        };
+        f.write_str(value)
+    }
+}
+
+/// Chroma siting, i.e. position of the chroma samples relative to luma.
+///
+/// 8-bit 4:2:0 streams carry it in the colorspace tag (`C420jpeg`,
+/// `C420mpeg2`, `C420paldv`), other streams may use the `YSCSS` vendor
+/// extension written by ffmpeg.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ChromaSiting {
+    /// Chroma sited between luma samples both horizontally and vertically
+    /// (biaxially-displaced, JPEG/MPEG-1 style).
+    Center,
+    /// Chroma co-sited with luma horizontally and centered vertically
+    /// (vertically-displaced, MPEG-2 style).
+    Left,
+    /// Cb co-sited with the top-left luma sample, Cr vertically displaced
+    /// by one row to the bottom-left one (PAL DV style).
+    PalDv,
+}
+
+impl ChromaSiting {
+    /// Parse a chroma siting from the `YSCSS` extension value.
+    pub fn parse(value: &[u8]) -> Result<ChromaSiting, Error> {
+        match value {
+            b"420JPEG" => Ok(ChromaSiting::Center),
+            b"420MPEG2" => Ok(ChromaSiting::Left),
+            b"420PALDV" => Ok(ChromaSiting::PalDv),
+            _ => parse_error!(ParseErrorKind::General),
+        }
+    }
+}
+
+impl fmt::Display for ChromaSiting {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let value = match *self {
+            ChromaSiting::Center => "420JPEG",
+            ChromaSiting::Left => "420MPEG2",
+            ChromaSiting::PalDv => "420PALDV",
+        };
+        f.write_str(value)
+    }
+}
+
+/// Color primaries, stored as `COLORPRIMARIES` vendor extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ColorPrimaries {
+    /// ITU-R BT.709.
+    Bt709,
+    /// ITU-R BT.470 System M.
+    Bt470m,
+    /// ITU-R BT.470 System B, G (BT.601 625-line).
+    Bt470bg,
+    /// SMPTE 170M (BT.601 525-line).
+    Smpte170m,
+    /// SMPTE 240M.
+    Smpte240m,
+    /// Generic film (color filters using Illuminant C).
+    Film,
+    /// ITU-R BT.2020 and BT.2100.
+    Bt2020,
+    /// SMPTE ST 428-1 (CIE 1931 XYZ).
+    Smpte428,
+    /// SMPTE RP 431-2 (DCI-P3).
+    Smpte431,
+    /// SMPTE EG 432-1 (Display P3).
+    Smpte432,
+    /// EBU Tech. 3213-E.
+    Ebu3213,
+}
+
+impl ColorPrimaries {
+    /// Parse a value of the `COLORPRIMARIES` extension.
+    pub fn parse(value: &[u8]) -> Result<ColorPrimaries, Error> {
+        match value {
+            b"BT709" => Ok(ColorPrimaries::Bt709),
+            b"BT470M" => Ok(ColorPrimaries::Bt470m),
+            b"BT470BG" => Ok(ColorPrimaries::Bt470bg),
+            b"SMPTE170M" => Ok(ColorPrimaries::Smpte170m),
+            b"SMPTE240M" => Ok(ColorPrimaries::Smpte240m),
+            b"FILM" => Ok(ColorPrimaries::Film),
+            b"BT2020" => Ok(ColorPrimaries::Bt2020),
+            b"SMPTE428" => Ok(ColorPrimaries::Smpte428),
+            b"SMPTE431" => Ok(ColorPrimaries::Smpte431),
+            b"SMPTE432" => Ok(ColorPrimaries::Smpte432),
+            b"EBU3213" => Ok(ColorPrimaries::Ebu3213),
+            _ => parse_error!(ParseErrorKind::General),
+        }
+    }
+}
+
+impl fmt::Display for ColorPrimaries {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let value = match *self {
+            ColorPrimaries::Bt709 => "BT709",
+            ColorPrimaries::Bt470m => "BT470M",
+            ColorPrimaries::Bt470bg => "BT470BG",
+            ColorPrimaries::Smpte170m => "SMPTE170M",
+            ColorPrimaries::Smpte240m => "SMPTE240M",
+            ColorPrimaries::Film => "FILM",
+            ColorPrimaries::Bt2020 => "BT2020",
+            ColorPrimaries::Smpte428 => "SMPTE428",
+            ColorPrimaries::Smpte431 => "SMPTE431",
+            ColorPrimaries::Smpte432 => "SMPTE432",
+            ColorPrimaries::Ebu3213 => "EBU3213",
+        };
+        f.write_str(value)
+    }
+}
+
+/// Transfer characteristics, stored as `COLORTRC` vendor extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TransferCharacteristics {
+    /// ITU-R BT.709.
+    Bt709,
+    /// Assumed display gamma 2.2 (BT.470 System M).
+    Gamma22,
+    /// Assumed display gamma 2.8 (BT.470 System B, G).
+    Gamma28,
+    /// SMPTE 170M (BT.601).
+    Smpte170m,
+    /// SMPTE 240M.
+    Smpte240m,
+    /// Linear transfer characteristics.
+    Linear,
+    /// IEC 61966-2-1 (sRGB).
+    Srgb,
+    /// ITU-R BT.2020 for 10-bit system.
+    Bt2020TenBit,
+    /// ITU-R BT.2020 for 12-bit system.
+    Bt2020TwelveBit,
+    /// SMPTE ST 2084 (PQ).
+    Smpte2084,
+    /// ARIB STD-B67 (HLG).
+    AribStdB67,
+}
+
+impl TransferCharacteristics {
+    /// Parse a value of the `COLORTRC` extension.
+    pub fn parse(value: &[u8]) -> Result<TransferCharacteristics, Error> {
+        match value {
+            b"BT709" => Ok(TransferCharacteristics::Bt709),
+            b"GAMMA22" => Ok(TransferCharacteristics::Gamma22),
+            b"GAMMA28" => Ok(TransferCharacteristics::Gamma28),
+            b"SMPTE170M" => Ok(TransferCharacteristics::Smpte170m),
+            b"SMPTE240M" => Ok(TransferCharacteristics::Smpte240m),
+            b"LINEAR" => Ok(TransferCharacteristics::Linear),
+            b"IEC61966-2-1" => Ok(TransferCharacteristics::Srgb),
+            b"BT2020-10" => Ok(TransferCharacteristics::Bt2020TenBit),
+            b"BT2020-12" => Ok(TransferCharacteristics::Bt2020TwelveBit),
+            b"SMPTE2084" => Ok(TransferCharacteristics::Smpte2084),
+            b"ARIB-STD-B67" => Ok(TransferCharacteristics::AribStdB67),
+            _ => parse_error!(ParseErrorKind::General),
+        }
+    }
+}
+
+impl fmt::Display for TransferCharacteristics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let value = match *self {
+            TransferCharacteristics::Bt709 => "BT709",
+            TransferCharacteristics::Gamma22 => "GAMMA22",
+            TransferCharacteristics::Gamma28 => "GAMMA28",
+            TransferCharacteristics::Smpte170m => "SMPTE170M",
+            TransferCharacteristics::Smpte240m => "SMPTE240M",
+            TransferCharacteristics::Linear => "LINEAR",
+            TransferCharacteristics::Srgb => "IEC61966-2-1",
+            TransferCharacteristics::Bt2020TenBit => "BT2020-10",
+            TransferCharacteristics::Bt2020TwelveBit => "BT2020-12",
+            TransferCharacteristics::Smpte2084 => "SMPTE2084",
+            TransferCharacteristics::AribStdB67 => "ARIB-STD-B67",
+        };
+        f.write_str(value)
+    }
+}
+
+/// Matrix coefficients, stored as `COLORMATRIX` vendor extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum MatrixCoefficients {
+    /// Identity matrix, i.e. planes contain G, B and R.
+    Identity,
+    /// ITU-R BT.709.
+    Bt709,
+    /// US FCC 73.682.
+    Fcc,
+    /// ITU-R BT.470 System B, G (BT.601 625-line).
+    Bt470bg,
+    /// SMPTE 170M (BT.601 525-line).
+    Smpte170m,
+    /// SMPTE 240M.
+    Smpte240m,
+    /// YCgCo.
+    Ycgco,
+    /// ITU-R BT.2020 non-constant luminance.
+    Bt2020Ncl,
+    /// ITU-R BT.2020 constant luminance.
+    Bt2020Cl,
+    /// ITU-R BT.2100 ICtCp.
+    Ictcp,
+}
+
+impl MatrixCoefficients {
+    /// Parse a value of the `COLORMATRIX` extension.
+    pub fn parse(value: &[u8]) -> Result<MatrixCoefficients, Error> {
+        match value {
+            b"GBR" => Ok(MatrixCoefficients::Identity),
+            b"BT709" => Ok(MatrixCoefficients::Bt709),
+            b"FCC" => Ok(MatrixCoefficients::Fcc),
+            b"BT470BG" => Ok(MatrixCoefficients::Bt470bg),
+            b"SMPTE170M" => Ok(MatrixCoefficients::Smpte170m),
+            b"SMPTE240M" => Ok(MatrixCoefficients::Smpte240m),
+            b"YCGCO" => Ok(MatrixCoefficients::Ycgco),
+            b"BT2020NC" => Ok(MatrixCoefficients::Bt2020Ncl),
+            b"BT2020C" => Ok(MatrixCoefficients::Bt2020Cl),
+            b"ICTCP" => Ok(MatrixCoefficients::Ictcp),
+            _ => parse_error!(ParseErrorKind::General),
+        }
+    }
+}
+
+impl fmt::Display for MatrixCoefficients {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let value = match *self {
+            MatrixCoefficients::Identity => "GBR",
+            MatrixCoefficients::Bt709 => "BT709",
+            MatrixCoefficients::Fcc => "FCC",
+            MatrixCoefficients::Bt470bg => "BT470BG",
+            MatrixCoefficients::Smpte170m => "SMPTE170M",
+            MatrixCoefficients::Smpte240m => "SMPTE240M",
+            MatrixCoefficients::Ycgco => "YCGCO",
+            MatrixCoefficients::Bt2020Ncl => "BT2020NC",
+            MatrixCoefficients::Bt2020Cl => "BT2020C",
+            MatrixCoefficients::Ictcp => "ICTCP",
+        };
+        f.write_str(value)
+    }
+}
+
+/// Color description of the stream: primaries, transfer characteristics and
+/// matrix coefficients. Each of them is stored as separate vendor extension
+/// and might be missing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ColorDescription {
+    /// Color primaries.
+    pub primaries: Option<ColorPrimaries>,
+    /// Transfer characteristics.
+    pub transfer: Option<TransferCharacteristics>,
+    /// Matrix coefficients.
+    pub matrix: Option<MatrixCoefficients>,
+}
+
 /// Colorspace (color model/pixel format). Only subset of them is supported.
 ///
 /// From libavformat/yuv4mpegenc.c:
 ///
 /// > yuv4mpeg can only handle yuv444p, yuv422p, yuv420p, yuv411p and gray8
-/// pixel formats. And using 'strict -1' also yuv444p9, yuv422p9, yuv420p9,
-/// yuv444p10, yuv422p10, yuv420p10, yuv444p12, yuv422p12, yuv420p12,
-/// yuv444p14, yuv422p14, yuv420p14, yuv444p16, yuv422p16, yuv420p16, gray9,
-/// gray10, gray12 and gray16 pixel formats.
+/// > pixel formats. And using 'strict -1' also yuv444p9, yuv422p9, yuv420p9,
+/// > yuv444p10, yuv422p10, yuv420p10, yuv444p12, yuv422p12, yuv420p12,
+/// > yuv444p14, yuv422p14, yuv420p14, yuv444p16, yuv422p16, yuv420p16, gray9,
+/// > gray10, gray12 and gray16 pixel formats.
 #[derive(Debug, Clone, Copy)]
 #[non_exhaustive]
 pub enum Colorspace {
     /// Grayscale only, 8-bit.
     Cmono,
+    /// Grayscale only, 9-bit.
+    Cmono9,
+    /// Grayscale only, 10-bit.
+    Cmono10,
     /// Grayscale only, 12-bit.
     Cmono12,
+    /// Grayscale only, 16-bit.
+    Cmono16,
     /// 4:2:0 with coincident chroma planes, 8-bit.
     C420,
+    /// 4:2:0 with coincident chroma planes, 9-bit.
+    C420p9,
     /// 4:2:0 with coincident chroma planes, 10-bit.
     C420p10,
     /// 4:2:0 with coincident chroma planes, 12-bit.
     C420p12,
+    /// 4:2:0 with coincident chroma planes, 14-bit.
+    C420p14,
+    /// 4:2:0 with coincident chroma planes, 16-bit.
+    C420p16,
     /// 4:2:0 with biaxially-displaced chroma planes, 8-bit.
     C420jpeg,
-    /// 4:2:0 with coincident Cb and vertically-displaced Cr, 8-bit.
+    /// 4:2:0 with coincident Cb and vertically-displaced Cr (sited with the
+    /// top-left and bottom-left luma samples respectively), 8-bit.
     C420paldv,
     /// 4:2:0 with vertically-displaced chroma planes, 8-bit.
     C420mpeg2,
+    /// 4:1:1, 8-bit.
+    C411,
     /// 4:2:2, 8-bit.
     C422,
+    /// 4:2:2, 9-bit.
+    C422p9,
     /// 4:2:2, 10-bit.
     C422p10,
     /// 4:2:2, 12-bit.
     C422p12,
+    /// 4:2:2, 14-bit.
+    C422p14,
+    /// 4:2:2, 16-bit.
+    C422p16,
     /// 4:4:4, 8-bit.
     C444,
+    /// 4:4:4, 9-bit.
+    C444p9,
     /// 4:4:4, 10-bit.
     C444p10,
     /// 4:4:4, 12-bit.
     C444p12,
+    /// 4:4:4, 14-bit.
+    C444p14,
+    /// 4:4:4, 16-bit.
+    C444p16,
+    /// 4:4:4 with an alpha plane, 8-bit.
+    C444alpha,
 }
 
 impl Colorspace {
@@ -268,16 +937,27 @@ impl Colorspace {
         match self {
             Colorspace::Cmono
             | Colorspace::C420
+            | Colorspace::C411
             | Colorspace::C422
             | Colorspace::C444
+            | Colorspace::C444alpha
             | Colorspace::C420jpeg
             | Colorspace::C420paldv
             | Colorspace::C420mpeg2 => 8,
-            Colorspace::C420p10 | Colorspace::C422p10 | Colorspace::C444p10 => 10,
+            Colorspace::Cmono9 | Colorspace::C420p9 | Colorspace::C422p9 | Colorspace::C444p9 => 9,
+            Colorspace::Cmono10
+            | Colorspace::C420p10
+            | Colorspace::C422p10
+            | Colorspace::C444p10 => 10,
             Colorspace::Cmono12
             | Colorspace::C420p12
             | Colorspace::C422p12
             | Colorspace::C444p12 => 12,
+            Colorspace::C420p14 | Colorspace::C422p14 | Colorspace::C444p14 => 14,
+            Colorspace::Cmono16
+            | Colorspace::C420p16
+            | Colorspace::C422p16
+            | Colorspace::C444p16 => 16,
         }
     }
 
@@ -290,32 +970,128 @@ impl Colorspace {
             2
         }
     }
-}
 
-fn get_plane_sizes(width: usize, height: usize, colorspace: Colorspace) -> (usize, usize, usize) {
-    let y_plane_size = width * height * colorspace.get_bytes_per_sample();
+    /// Return the chroma siting implied by the colorspace, if any.
+    #[inline]
+    pub fn get_chroma_siting(self) -> Option<ChromaSiting> {
+        match self {
+            Colorspace::C420jpeg => Some(ChromaSiting::Center),
+            Colorspace::C420mpeg2 => Some(ChromaSiting::Left),
+            Colorspace::C420paldv => Some(ChromaSiting::PalDv),
+            _ => None,
+        }
+    }
+
+    // Return 8-bit 4:2:0 colorspace with the given siting. `None` for
+    // colorspaces which can't express the siting.
+    fn with_chroma_siting(self, chroma_siting: ChromaSiting) -> Option<Colorspace> {
+        match self {
+            Colorspace::C420
+            | Colorspace::C420jpeg
+            | Colorspace::C420mpeg2
+            | Colorspace::C420paldv => Some(match chroma_siting {
+                ChromaSiting::Center => Colorspace::C420jpeg,
+                ChromaSiting::Left => Colorspace::C420mpeg2,
+                ChromaSiting::PalDv => Colorspace::C420paldv,
+            }),
+            _ => None,
+        }
+    }
+
+    // Whether chroma is subsampled 2x both horizontally and vertically, i.e.
+    // chroma siting is meaningful.
+    fn is_420(self) -> bool {
+        matches!(
+            self,
+            Colorspace::C420
+                | Colorspace::C420p9
+                | Colorspace::C420p10
+                | Colorspace::C420p12
+                | Colorspace::C420p14
+                | Colorspace::C420p16
+                | Colorspace::C420jpeg
+                | Colorspace::C420paldv
+                | Colorspace::C420mpeg2
+        )
+    }
+}
 
-    let c420_chroma_size =
-        ((width + 1) / 2) * ((height + 1) / 2) * colorspace.get_bytes_per_sample();
-    let c422_chroma_size = ((width + 1) / 2) * height * colorspace.get_bytes_per_sample();
+/// Return dimensions in samples (width, height) of Y, U, V and A planes of a
+/// frame with the given dimensions and colorspace. Missing planes have zero
+/// dimensions.
+pub fn get_plane_dimensions(
+    width: usize,
+    height: usize,
+    colorspace: Colorspace,
+) -> [(usize, usize); 4] {
+    let luma = (width, height);
+    let none = (0, 0);
 
-    let c420_sizes = (y_plane_size, c420_chroma_size, c420_chroma_size);
-    let c422_sizes = (y_plane_size, c422_chroma_size, c422_chroma_size);
-    let c444_sizes = (y_plane_size, y_plane_size, y_plane_size);
+    let c420_chroma = (width.div_ceil(2), height.div_ceil(2));
+    let c422_chroma = (width.div_ceil(2), height);
+    let c411_chroma = (width.div_ceil(4), height);
 
     match colorspace {
-        Colorspace::Cmono | Colorspace::Cmono12 => (y_plane_size, 0, 0),
+        Colorspace::Cmono
+        | Colorspace::Cmono9
+        | Colorspace::Cmono10
+        | Colorspace::Cmono12
+        | Colorspace::Cmono16 => [luma, none, none, none],
         Colorspace::C420
+        | Colorspace::C420p9
         | Colorspace::C420p10
         | Colorspace::C420p12
+        | Colorspace::C420p14
+        | Colorspace::C420p16
         | Colorspace::C420jpeg
         | Colorspace::C420paldv
-        | Colorspace::C420mpeg2 => c420_sizes,
-        Colorspace::C422 | Colorspace::C422p10 | Colorspace::C422p12 => c422_sizes,
-        Colorspace::C444 | Colorspace::C444p10 | Colorspace::C444p12 => c444_sizes,
+        | Colorspace::C420mpeg2 => [luma, c420_chroma, c420_chroma, none],
+        Colorspace::C411 => [luma, c411_chroma, c411_chroma, none],
+        Colorspace::C422
+        | Colorspace::C422p9
+        | Colorspace::C422p10
+        | Colorspace::C422p12
+        | Colorspace::C422p14
+        | Colorspace::C422p16 => [luma, c422_chroma, c422_chroma, none],
+        Colorspace::C444
+        | Colorspace::C444p9
+        | Colorspace::C444p10
+        | Colorspace::C444p12
+        | Colorspace::C444p14
+        | Colorspace::C444p16 => [luma, luma, luma, none],
+        Colorspace::C444alpha => [luma, luma, luma, luma],
     }
 }
 
+/// Return sizes in bytes of Y, U, V and A planes of a frame with the given
+/// dimensions and colorspace. Missing planes have zero size.
+///
+/// # Panics
+///
+/// Panics if the frame size doesn't fit in `usize`.
+pub fn get_plane_sizes(
+    width: usize,
+    height: usize,
+    colorspace: Colorspace,
+) -> (usize, usize, usize, usize) {
+    let [y, u, v, a] = checked_plane_sizes(width, height, colorspace).expect("frame size overflow");
+    (y, u, v, a)
+}
+
+// Same as `get_plane_sizes` but returns `None` if the frame size overflows.
+fn checked_plane_sizes(width: usize, height: usize, colorspace: Colorspace) -> Option<[usize; 4]> {
+    let bytes_per_sample = colorspace.get_bytes_per_sample();
+    let size =
+        |(width, height): (usize, usize)| width.checked_mul(height)?.checked_mul(bytes_per_sample);
+    let [y, u, v, a] = get_plane_dimensions(width, height, colorspace);
+    let plane_lens = [size(y)?, size(u)?, size(v)?, size(a)?];
+    // Frame size including the bare frame header must fit as well.
+    plane_lens
+        .iter()
+        .try_fold(FRAME_MAGICK.len() + 1, |sum, &len| sum.checked_add(len))?;
+    Some(plane_lens)
+}
+
 /// Limits on the resources `Decoder` is allowed to use.
 #[derive(Clone, Copy, Debug)]
 pub struct Limits {
@@ -331,179 +1107,1217 @@ impl Default for Limits {
     }
 }
 
-/// YUV4MPEG2 decoder.
-pub struct Decoder<R: Read> {
-    reader: R,
-    params_buf: Vec<u8>,
-    frame_buf: Vec<u8>,
+/// Parsed file header.
+#[derive(Debug, Clone)]
+pub struct Header {
     raw_params: Vec<u8>,
     width: usize,
     height: usize,
     framerate: Ratio,
     pixel_aspect: Ratio,
     colorspace: Colorspace,
-    y_len: usize,
-    u_len: usize,
+    interlacing: Interlacing,
+    color_range: Option<ColorRange>,
+    chroma_siting: Option<ChromaSiting>,
+    color_description: ColorDescription,
+    plane_lens: [usize; 4],
+    frame_size: usize,
 }
 
-impl<R: Read> Decoder<R> {
-    /// Create a new decoder instance.
-    pub fn new(reader: R) -> Result<Decoder<R>, Error> {
-        Decoder::new_with_limits(reader, Limits::default())
+impl Header {
+    /// Return file width.
+    #[inline]
+    pub fn get_width(&self) -> usize {
+        self.width
+    }
+    /// Return file height.
+    #[inline]
+    pub fn get_height(&self) -> usize {
+        self.height
+    }
+    /// Return file framerate.
+    #[inline]
+    pub fn get_framerate(&self) -> Ratio {
+        self.framerate
+    }
+    /// Return file pixel aspect.
+    #[inline]
+    pub fn get_pixel_aspect(&self) -> Ratio {
+        self.pixel_aspect
+    }
+    /// Return file colorspace.
+    ///
+    /// **NOTE:** normally all .y4m should have colorspace param, but there are
+    /// files encoded without that tag and it's unclear what should we do in
+    /// that case. Currently C420 is implied by default as per ffmpeg behavior.
+    #[inline]
+    pub fn get_colorspace(&self) -> Colorspace {
+        self.colorspace
+    }
+    /// Return file interlacing mode.
+    ///
+    /// Progressive is implied if the file has no interlacing tag.
+    #[inline]
+    pub fn get_interlacing(&self) -> Interlacing {
+        self.interlacing
+    }
+    /// Return file color range if specified with `COLORRANGE` extension.
+    #[inline]
+    pub fn get_color_range(&self) -> Option<ColorRange> {
+        self.color_range
+    }
+    /// Return file chroma siting if specified either by colorspace or with
+    /// `YSCSS` extension. The extension is ignored unless the colorspace
+    /// is 4:2:0.
+    #[inline]
+    pub fn get_chroma_siting(&self) -> Option<ChromaSiting> {
+        self.chroma_siting
+    }
+    /// Return file color description specified with `COLORPRIMARIES`,
+    /// `COLORTRC` and `COLORMATRIX` extensions.
+    #[inline]
+    pub fn get_color_description(&self) -> ColorDescription {
+        self.color_description
+    }
+    /// Return file raw parameters.
+    #[inline]
+    pub fn get_raw_params(&self) -> &[u8] {
+        &self.raw_params
+    }
+    /// Return the bit depth per sample
+    #[inline]
+    pub fn get_bit_depth(&self) -> usize {
+        self.colorspace.get_bit_depth()
+    }
+    /// Return the number of bytes in a sample
+    #[inline]
+    pub fn get_bytes_per_sample(&self) -> usize {
+        self.colorspace.get_bytes_per_sample()
+    }
+    /// Return the size in bytes of the frame data (all planes).
+    #[inline]
+    pub fn get_frame_size(&self) -> usize {
+        self.frame_size
     }
 
-    /// Create a new decoder instance with custom limits.
-    pub fn new_with_limits(mut reader: R, limits: Limits) -> Result<Decoder<R>, Error> {
-        let mut params_buf = vec![0; MAX_PARAMS_SIZE];
-        let end_params_pos = reader.read_until(TERMINATOR, &mut params_buf)?;
-        if end_params_pos < FILE_MAGICK.len() || !params_buf.starts_with(FILE_MAGICK) {
-            parse_error!(ParseError::InvalidY4M)
+    // Parse file header line without terminator.
+    fn parse(line: &[u8]) -> Result<Header, Error> {
+        if !line.starts_with(FILE_MAGICK) {
+            return Err(Error::ParseError(
+                ParseError::new(ParseErrorKind::InvalidY4M).at(0),
+            ));
         }
-        let raw_params = params_buf[FILE_MAGICK.len()..end_params_pos].to_owned();
+        let raw_params = line[FILE_MAGICK.len()..].to_owned();
         let mut width = 0;
         let mut height = 0;
+        // Offset and value of the `W` and `H` tags.
+        let mut width_tag = None;
+        let mut height_tag = None;
         // Framerate is actually required per spec, but let's be a bit more
         // permissive as per ffmpeg behavior.
         let mut framerate = Ratio::new(25, 1);
         let mut pixel_aspect = Ratio::new(1, 1);
         let mut colorspace = None;
+        let mut interlacing = Interlacing::Progressive;
+        let mut color_range = None;
+        let mut chroma_siting = None;
+        let mut color_description = ColorDescription::default();
+        let mut tag_offset = FILE_MAGICK.len() as u64;
         // We shouldn't convert it to string because encoding is unspecified.
         for param in raw_params.split(|&b| b == FIELD_SEP) {
+            let param_offset = tag_offset;
+            tag_offset += param.len() as u64 + 1;
             if param.is_empty() {
                 continue;
             }
             let (name, value) = (param[0], &param[1..]);
-            // TODO(Kagami): interlacing, comment.
+            let context =
+                |err: Error| err.with_parse_context(|e| e.with_tag(name, value).at(param_offset));
             match name {
-                b'W' => width = parse_bytes(value)?,
-                b'H' => height = parse_bytes(value)?,
-                b'F' => framerate = Ratio::parse(value)?,
-                b'A' => pixel_aspect = Ratio::parse(value)?,
-                b'C' => {
-                    colorspace = match value {
-                        b"mono" => Some(Colorspace::Cmono),
-                        b"mono12" => Some(Colorspace::Cmono12),
-                        b"420" => Some(Colorspace::C420),
+                b'W' => {
+                    width = parse_bytes(value).map_err(context)?;
+                    width_tag = Some((param_offset, value));
+                }
+                b'H' => {
+                    height = parse_bytes(value).map_err(context)?;
+                    height_tag = Some((param_offset, value));
+                }
+                b'F' => framerate = Ratio::parse(value).map_err(context)?,
+                b'I' => interlacing = Interlacing::parse(value).map_err(context)?,
+                b'X' if value.starts_with(COLOR_RANGE_EXT) => {
+                    // Be permissive about unknown values like ffmpeg does.
+                    color_range = ColorRange::parse(&value[COLOR_RANGE_EXT.len()..]).ok()
+                }
+                b'X' if value.starts_with(CHROMA_SITING_EXT) => {
+                    chroma_siting = ChromaSiting::parse(&value[CHROMA_SITING_EXT.len()..]).ok()
+                }
+                b'X' if value.starts_with(COLOR_PRIMARIES_EXT) => {
+                    color_description.primaries =
+                        ColorPrimaries::parse(&value[COLOR_PRIMARIES_EXT.len()..]).ok()
+                }
+                b'X' if value.starts_with(COLOR_TRC_EXT) => {
+                    color_description.transfer =
+                        TransferCharacteristics::parse(&value[COLOR_TRC_EXT.len()..]).ok()
+                }
+                b'X' if value.starts_with(COLOR_MATRIX_EXT) => {
+                    color_description.matrix =
+                        MatrixCoefficients::parse(&value[COLOR_MATRIX_EXT.len()..]).ok()
+                }
+                b'A' => pixel_aspect = Ratio::parse(value).map_err(context)?,
+                b'C' => {
+                    colorspace = match value {
+                        b"mono" => Some(Colorspace::Cmono),
+                        b"mono9" => Some(Colorspace::Cmono9),
+                        b"mono10" => Some(Colorspace::Cmono10),
+                        b"mono12" => Some(Colorspace::Cmono12),
+                        b"mono16" => Some(Colorspace::Cmono16),
+                        b"420" => Some(Colorspace::C420),
+                        b"420p9" => Some(Colorspace::C420p9),
                         b"420p10" => Some(Colorspace::C420p10),
                         b"420p12" => Some(Colorspace::C420p12),
+                        b"420p14" => Some(Colorspace::C420p14),
+                        b"420p16" => Some(Colorspace::C420p16),
+                        b"411" => Some(Colorspace::C411),
                         b"422" => Some(Colorspace::C422),
+                        b"422p9" => Some(Colorspace::C422p9),
                         b"422p10" => Some(Colorspace::C422p10),
                         b"422p12" => Some(Colorspace::C422p12),
+                        b"422p14" => Some(Colorspace::C422p14),
+                        b"422p16" => Some(Colorspace::C422p16),
                         b"444" => Some(Colorspace::C444),
+                        b"444p9" => Some(Colorspace::C444p9),
                         b"444p10" => Some(Colorspace::C444p10),
                         b"444p12" => Some(Colorspace::C444p12),
+                        b"444p14" => Some(Colorspace::C444p14),
+                        b"444p16" => Some(Colorspace::C444p16),
+                        b"444alpha" => Some(Colorspace::C444alpha),
                         b"420jpeg" => Some(Colorspace::C420jpeg),
                         b"420paldv" => Some(Colorspace::C420paldv),
                         b"420mpeg2" => Some(Colorspace::C420mpeg2),
-                        _ => return Err(Error::UnknownColorspace),
+                        _ => {
+                            return Err(Error::ParseError(
+                                ParseError::new(ParseErrorKind::UnknownColorspace)
+                                    .with_tag(name, value)
+                                    .at(param_offset),
+                            ))
+                        }
                     }
                 }
                 _ => {}
             }
         }
         let colorspace = colorspace.unwrap_or(Colorspace::C420);
-        if width == 0 || height == 0 {
-            parse_error!(ParseError::General)
-        }
-        let (y_len, u_len, v_len) = get_plane_sizes(width, height, colorspace);
-        let frame_size = y_len + u_len + v_len;
-        if frame_size > limits.bytes {
-            return Err(Error::OutOfMemory);
+        // Colorspace tag takes precedence over the extension, which is only
+        // meaningful for 4:2:0 colorspaces.
+        let chroma_siting = colorspace
+            .get_chroma_siting()
+            .or(chroma_siting.filter(|_| colorspace.is_420()));
+        for &(name, size, tag) in &[(b'W', width, width_tag), (b'H', height, height_tag)] {
+            let err = match tag {
+                None => ParseError::new(ParseErrorKind::MissingTag).with_tag(name, &[]),
+                Some((offset, value)) if size == 0 => ParseError::new(ParseErrorKind::General)
+                    .with_tag(name, value)
+                    .at(offset),
+                Some(_) => continue,
+            };
+            return Err(Error::ParseError(err));
         }
-        let frame_buf = vec![0; frame_size];
-        Ok(Decoder {
-            reader,
-            params_buf,
-            frame_buf,
+        let plane_lens =
+            checked_plane_sizes(width, height, colorspace).ok_or(Error::OutOfMemory)?;
+        Ok(Header {
             raw_params,
             width,
             height,
             framerate,
             pixel_aspect,
             colorspace,
-            y_len,
-            u_len,
+            interlacing,
+            color_range,
+            chroma_siting,
+            color_description,
+            plane_lens,
+            frame_size: plane_lens.iter().sum(),
+        })
+    }
+
+    // Split frame data into Y, U, V and A planes.
+    fn split_planes<'f>(&self, data: &'f [u8]) -> [&'f [u8]; 4] {
+        let (y_end, u_end) = (self.plane_lens[0], self.plane_lens[0] + self.plane_lens[1]);
+        let v_end = u_end + self.plane_lens[2];
+        [
+            &data[0..y_end],
+            &data[y_end..u_end],
+            &data[u_end..v_end],
+            &data[v_end..self.frame_size],
+        ]
+    }
+
+    // FNV-1a hash of the header parameters, stored in `FrameIndex` to match it
+    // against the stream.
+    fn hash(&self) -> u64 {
+        self.raw_params
+            .iter()
+            .fold(0xcbf2_9ce4_8422_2325, |hash, &b| {
+                (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+            })
+    }
+
+    // Size of the frame with bare header.
+    fn frame_stride(&self) -> usize {
+        FRAME_MAGICK.len() + 1 + self.frame_size
+    }
+}
+
+// Return position of the header line terminator in the in-memory stream.
+fn find_line_end(data: &[u8]) -> Result<usize, Error> {
+    let limit = data.len().min(MAX_PARAMS_SIZE);
+    match data[..limit].iter().position(|&b| b == TERMINATOR) {
+        Some(pos) => Ok(pos),
+        None if data.len() < MAX_PARAMS_SIZE => Err(Error::TruncatedHeader { read: data.len() }),
+        None => parse_error!(ParseErrorKind::General),
+    }
+}
+
+// Parse file header of the in-memory stream and return it with the offset of
+// the first frame.
+fn parse_header_slice(data: &[u8]) -> Result<(Header, usize), Error> {
+    if data.is_empty() {
+        return Err(Error::EOF);
+    }
+    let end_params_pos = find_line_end(data).map_err(|err| err.with_parse_context(|e| e.at(0)))?;
+    let header = Header::parse(&data[..end_params_pos])?;
+    Ok((header, end_params_pos + 1))
+}
+
+// Parse frame of the in-memory stream at the given offset and return frame
+// raw params and range of the frame data.
+fn parse_frame_slice(
+    data: &[u8],
+    offset: usize,
+    frame_index: usize,
+    header: &Header,
+) -> Result<(Option<Vec<u8>>, Range<usize>), Error> {
+    let context =
+        |err: Error| err.with_parse_context(|e| e.at(offset as u64).in_frame(frame_index));
+    let data = &data[offset..];
+    if data.is_empty() {
+        return Err(Error::EOF);
+    }
+    let end_params_pos = match find_line_end(data) {
+        Err(Error::TruncatedHeader { read }) => return Err(Error::TruncatedFrameHeader { read }),
+        res => res.map_err(context)?,
+    };
+    let raw_params = parse_frame_header(&data[..end_params_pos]).map_err(context)?;
+    let start = end_params_pos + 1;
+    let read = data.len() - start;
+    if read < header.frame_size {
+        return Err(Error::TruncatedFrame {
+            read,
+            expected: header.frame_size,
+        });
+    }
+    Ok((
+        raw_params,
+        offset + start..offset + start + header.frame_size,
+    ))
+}
+
+// Parse file header from the result of reading its line and return it with
+// the line length.
+fn parse_header_line(
+    line: Result<usize, Error>,
+    buf: &[u8],
+    limits: Limits,
+) -> Result<(Header, usize), Error> {
+    let end_params_pos = match line {
+        Err(Error::TruncatedHeader { read: 0 }) => return Err(Error::EOF),
+        res => res.map_err(|err| err.with_parse_context(|e| e.at(0)))?,
+    };
+    let header = Header::parse(&buf[..end_params_pos])?;
+    if header.frame_size > limits.bytes {
+        return Err(Error::OutOfMemory);
+    }
+    Ok((header, end_params_pos))
+}
+
+// Parse frame header from the result of reading its line and return frame raw
+// params with the line length.
+fn parse_frame_header_line(
+    line: Result<usize, Error>,
+    buf: &[u8],
+    offset: u64,
+    frame_index: usize,
+) -> Result<(Option<Vec<u8>>, usize), Error> {
+    let context = |err: Error| err.with_parse_context(|e| e.at(offset).in_frame(frame_index));
+    let end_params_pos = match line {
+        Err(Error::TruncatedHeader { read: 0 }) => return Err(Error::EOF),
+        Err(Error::TruncatedHeader { read }) => return Err(Error::TruncatedFrameHeader { read }),
+        res => res.map_err(context)?,
+    };
+    let raw_params = parse_frame_header(&buf[..end_params_pos]).map_err(context)?;
+    Ok((raw_params, end_params_pos))
+}
+
+// Parse frame header line without terminator and return frame raw params.
+fn parse_frame_header(line: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+    if !line.starts_with(FRAME_MAGICK) {
+        parse_error!(ParseErrorKind::InvalidY4M)
+    }
+    // Frame params are parsed lazily by `Frame::get_params`.
+    let params = &line[FRAME_MAGICK.len()..];
+    if params.is_empty() {
+        return Ok(None);
+    }
+    // Check for extra space.
+    if params[0] != FIELD_SEP {
+        parse_error!(ParseErrorKind::InvalidY4M)
+    }
+    Ok(Some(params[1..].to_owned()))
+}
+
+/// YUV4MPEG2 decoder.
+///
+/// Input is buffered internally, so there is no need to wrap the reader in
+/// `BufReader`. The underlying reader may be read past the last decoded
+/// frame, the data read ahead is kept in the buffer returned by
+/// `into_inner`.
+#[cfg(feature = "std")]
+pub struct Decoder<R: Read> {
+    reader: BufReader<R>,
+    params_buf: Vec<u8>,
+    // Allocated on first `read_frame` call.
+    frame_buf: Vec<u8>,
+    header: Header,
+    // Stream offset and index of the next frame.
+    offset: u64,
+    frame_index: usize,
+    // Stream offset of the first frame.
+    data_offset: u64,
+    layout: FrameLayout,
+    // Position of the stream start in the seekable reader, found on first
+    // seek. Stream offsets are relative to it.
+    base_offset: Option<u64>,
+}
+
+// Layout of frames in a seekable stream, detected on first seek.
+enum FrameLayout {
+    Unknown,
+    // All frame headers are bare, so frames have the same size.
+    Fixed(usize),
+    // Offsets and parameters of all frames.
+    Indexed(FrameIndex),
+}
+
+#[cfg(feature = "std")]
+impl<R: Read> Decoder<R> {
+    /// Create a new decoder instance.
+    pub fn new(reader: R) -> Result<Decoder<R>, Error> {
+        Decoder::new_with_limits(reader, Limits::default())
+    }
+
+    /// Create a new decoder instance with custom limits.
+    pub fn new_with_limits(reader: R, limits: Limits) -> Result<Decoder<R>, Error> {
+        Decoder::from_buf_reader(BufReader::new(reader), limits)
+    }
+
+    /// Create a new decoder instance over an already buffered reader, so the
+    /// input isn't buffered twice. The stream starts at the current position
+    /// of the reader, including any data already buffered in it.
+    pub fn from_buf_reader(mut reader: BufReader<R>, limits: Limits) -> Result<Decoder<R>, Error> {
+        let mut params_buf = vec![0; MAX_PARAMS_SIZE];
+        let line = reader.read_until_byte(TERMINATOR, &mut params_buf);
+        let (header, end_params_pos) = parse_header_line(line, &params_buf, limits)?;
+        Ok(Decoder {
+            reader,
+            params_buf,
+            frame_buf: Vec::new(),
+            header,
+            offset: end_params_pos as u64 + 1,
+            frame_index: 0,
+            data_offset: end_params_pos as u64 + 1,
+            layout: FrameLayout::Unknown,
+            base_offset: None,
         })
     }
 
     /// Iterate over frames. End of input is indicated by `Error::EOF`.
-    pub fn read_frame(&mut self) -> Result<Frame, Error> {
-        let end_params_pos = self.reader.read_until(TERMINATOR, &mut self.params_buf)?;
-        if end_params_pos < FRAME_MAGICK.len() || !self.params_buf.starts_with(FRAME_MAGICK) {
-            parse_error!(ParseError::InvalidY4M)
-        }
-        // We don't parse frame params currently but user has access to them.
-        let start_params_pos = FRAME_MAGICK.len();
-        let raw_params = if end_params_pos - start_params_pos > 0 {
-            // Check for extra space.
-            if self.params_buf[start_params_pos] != FIELD_SEP {
-                parse_error!(ParseError::InvalidY4M)
-            }
-            Some(self.params_buf[start_params_pos + 1..end_params_pos].to_owned())
-        } else {
-            None
-        };
-        self.reader.read_exact(&mut self.frame_buf)?;
-        Ok(Frame::new(
-            [
-                &self.frame_buf[0..self.y_len],
-                &self.frame_buf[self.y_len..self.y_len + self.u_len],
-                &self.frame_buf[self.y_len + self.u_len..],
-            ],
+    ///
+    /// `Error::EOF` is returned only if the stream ends at a frame boundary,
+    /// otherwise one of the truncation errors is returned.
+    pub fn read_frame(&mut self) -> Result<Frame<'_>, Error> {
+        let raw_params = self.read_frame_header()?;
+        if self.frame_buf.is_empty() {
+            self.frame_buf = vec![0; self.header.frame_size];
+        }
+        read_frame_data(&mut self.reader, &mut self.frame_buf)?;
+        self.finish_frame();
+        Ok(Frame::new_with_alpha(
+            self.header.split_planes(&self.frame_buf),
             raw_params,
         ))
     }
 
-    /// Return file width.
-    #[inline]
-    pub fn get_width(&self) -> usize {
-        self.width
+    /// Read next frame into the caller-provided buffer, reusing its
+    /// allocation. End of input is indicated by `Error::EOF`.
+    pub fn read_frame_buf(&mut self, frame: &mut FrameBuf) -> Result<(), Error> {
+        let raw_params = self.read_frame_header()?;
+        frame.reset(self.header.plane_lens, raw_params);
+        read_frame_data(&mut self.reader, &mut frame.data)?;
+        self.finish_frame();
+        Ok(())
     }
-    /// Return file height.
-    #[inline]
-    pub fn get_height(&self) -> usize {
-        self.height
+
+    /// Read next frame directly into the caller-provided buffer and return
+    /// frame raw parameters if any. End of input is indicated by
+    /// `Error::EOF`.
+    ///
+    /// Buffer must be exactly `get_frame_size` bytes long, otherwise
+    /// `Error::BadInput` is returned. Planes are stored one after another in
+    /// Y, U, V, A order with sizes as returned by `get_plane_sizes`.
+    pub fn read_frame_into(&mut self, buf: &mut [u8]) -> Result<Option<Vec<u8>>, Error> {
+        if buf.len() != self.header.frame_size {
+            return Err(Error::BadInput);
+        }
+        let raw_params = self.read_frame_header()?;
+        read_frame_data(&mut self.reader, buf)?;
+        self.finish_frame();
+        Ok(raw_params)
     }
-    /// Return file framerate.
-    #[inline]
-    pub fn get_framerate(&self) -> Ratio {
-        self.framerate
+
+    // Read frame header and return frame raw params.
+    fn read_frame_header(&mut self) -> Result<Option<Vec<u8>>, Error> {
+        let line = self
+            .reader
+            .read_until_byte(TERMINATOR, &mut self.params_buf);
+        let (raw_params, end_params_pos) =
+            parse_frame_header_line(line, &self.params_buf, self.offset, self.frame_index)?;
+        self.offset += end_params_pos as u64 + 1;
+        Ok(raw_params)
     }
-    /// Return file pixel aspect.
-    #[inline]
-    pub fn get_pixel_aspect(&self) -> Ratio {
-        self.pixel_aspect
+
+    // Account frame data once it's successfully read.
+    fn finish_frame(&mut self) {
+        self.offset += self.header.frame_size as u64;
+        self.frame_index += 1;
     }
-    /// Return file colorspace.
+
+    /// Return the underlying buffered reader. Data following the last decoded
+    /// frame is preserved in it, e.g. to read the next concatenated stream with
+    /// `from_buf_reader`.
+    pub fn into_inner(self) -> BufReader<R> {
+        self.reader
+    }
+
+    header_getters!();
+
+    /// Return a lending iterator over frames borrowing the decoder buffer.
+    pub fn frames(&mut self) -> Frames<'_, R> {
+        Frames {
+            decoder: self,
+            done: false,
+        }
+    }
+
+    /// Return an iterator over owned frames. Every frame is copied into a
+    /// new buffer.
+    pub fn owned_frames(&mut self) -> OwnedFrames<'_, R> {
+        OwnedFrames {
+            decoder: self,
+            done: false,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: Read + Seek> Decoder<R> {
+    /// Return the number of complete frames in the stream.
     ///
-    /// **NOTE:** normally all .y4m should have colorspace param, but there are
-    /// files encoded without that tag and it's unclear what should we do in
-    /// that case. Currently C420 is implied by default as per ffmpeg behavior.
+    /// The stream is scanned once to find the frame headers. If they all are
+    /// bare (`FRAME` without parameters), frames are located by their size,
+    /// otherwise an index of frame offsets is kept.
+    pub fn frame_count(&mut self) -> Result<usize, Error> {
+        self.detect_layout()?;
+        match self.layout {
+            FrameLayout::Fixed(count) => Ok(count),
+            FrameLayout::Indexed(ref index) => Ok(index.len()),
+            FrameLayout::Unknown => unreachable!(),
+        }
+    }
+
+    /// Seek to the given frame, so the next `read_frame` call returns it.
+    /// Seeking to `frame_count` is allowed, reading then returns
+    /// `Error::EOF`. Seeking past that returns `Error::BadInput`.
+    pub fn seek_to_frame(&mut self, frame_index: usize) -> Result<(), Error> {
+        self.detect_layout()?;
+        let offset = match self.layout {
+            FrameLayout::Fixed(count) if frame_index <= count => {
+                self.data_offset + (frame_index * self.header.frame_stride()) as u64
+            }
+            FrameLayout::Indexed(ref index) if frame_index <= index.len() => {
+                match index.get(frame_index) {
+                    Some(entry) => entry.offset,
+                    None => index.stream_len,
+                }
+            }
+            _ => return Err(Error::BadInput),
+        };
+        self.seek_to(offset)?;
+        self.offset = offset;
+        self.frame_index = frame_index;
+        Ok(())
+    }
+
+    /// Return the index of all frames in the stream, building it if needed.
+    ///
+    /// The index can be saved with `FrameIndex::write_to` and loaded later
+    /// with `set_frame_index` to avoid scanning the stream again.
+    pub fn build_frame_index(&mut self) -> Result<FrameIndex, Error> {
+        self.detect_layout()?;
+        match self.layout {
+            FrameLayout::Fixed(count) => {
+                let stream_len = self.stream_end()?;
+                self.seek_to(self.offset)?;
+                let stride = self.header.frame_stride() as u64;
+                let entries = (0..count as u64)
+                    .map(|i| FrameIndexEntry {
+                        offset: self.data_offset + i * stride,
+                        raw_params: None,
+                    })
+                    .collect();
+                Ok(FrameIndex {
+                    header_hash: self.header.hash(),
+                    data_offset: self.data_offset,
+                    frame_size: self.header.frame_size as u64,
+                    stream_len,
+                    entries,
+                })
+            }
+            FrameLayout::Indexed(ref index) => Ok(index.clone()),
+            FrameLayout::Unknown => unreachable!(),
+        }
+    }
+
+    /// Use a previously built frame index for seeking.
+    ///
+    /// `Error::BadInput` is returned if the index doesn't match the stream
+    /// (different header, frame size or stream length).
+    pub fn set_frame_index(&mut self, index: FrameIndex) -> Result<(), Error> {
+        let stream_len = self.stream_end()?;
+        self.seek_to(self.offset)?;
+        if index.header_hash != self.header.hash()
+            || index.data_offset != self.data_offset
+            || index.frame_size != self.header.frame_size as u64
+            || index.stream_len != stream_len
+        {
+            return Err(Error::BadInput);
+        }
+        self.layout = FrameLayout::Indexed(index);
+        Ok(())
+    }
+
+    // The reader may not start at the beginning of the y4m stream, so its
+    // position at the current stream offset is recorded once.
+    fn base_offset(&mut self) -> Result<u64, Error> {
+        if let Some(base_offset) = self.base_offset {
+            return Ok(base_offset);
+        }
+        let position = self.reader.stream_position()?;
+        let base_offset = position.checked_sub(self.offset).ok_or(Error::BadInput)?;
+        self.base_offset = Some(base_offset);
+        Ok(base_offset)
+    }
+
+    // Seek to the given stream offset.
+    fn seek_to(&mut self, offset: u64) -> Result<(), Error> {
+        let base_offset = self.base_offset()?;
+        self.reader.seek(SeekFrom::Start(base_offset + offset))?;
+        Ok(())
+    }
+
+    // Return stream offset of the end of the stream.
+    fn stream_end(&mut self) -> Result<u64, Error> {
+        let base_offset = self.base_offset()?;
+        Ok(self.reader.seek(SeekFrom::End(0))? - base_offset)
+    }
+
+    fn detect_layout(&mut self) -> Result<(), Error> {
+        if let FrameLayout::Unknown = self.layout {
+            self.base_offset()?;
+            let (offset, frame_index) = (self.offset, self.frame_index);
+            let res = self.detect_layout_at_start();
+            // Restore stream position regardless of the result.
+            self.seek_to(offset)?;
+            self.offset = offset;
+            self.frame_index = frame_index;
+            self.layout = res?;
+        }
+        Ok(())
+    }
+
+    fn detect_layout_at_start(&mut self) -> Result<FrameLayout, Error> {
+        let stream_len = self.stream_end()?;
+        let mut entries = Vec::new();
+        self.seek_to(self.data_offset)?;
+        self.offset = self.data_offset;
+        loop {
+            let offset = self.offset;
+            let raw_params = match self.read_frame_header() {
+                Ok(raw_params) => raw_params,
+                Err(Error::EOF) | Err(Error::TruncatedFrameHeader { .. }) => break,
+                Err(err) => return Err(err),
+            };
+            // Skip incomplete last frame.
+            if self.offset + self.header.frame_size as u64 > stream_len {
+                break;
+            }
+            entries.push(FrameIndexEntry { offset, raw_params });
+            self.offset += self.header.frame_size as u64;
+            self.seek_to(self.offset)?;
+            self.frame_index += 1;
+        }
+        // Frames with bare headers all have the same size.
+        if entries.iter().all(|entry| entry.raw_params.is_none()) {
+            return Ok(FrameLayout::Fixed(entries.len()));
+        }
+        Ok(FrameLayout::Indexed(FrameIndex {
+            header_hash: self.header.hash(),
+            data_offset: self.data_offset,
+            frame_size: self.header.frame_size as u64,
+            stream_len,
+            entries,
+        }))
+    }
+}
+
+/// YUV4MPEG2 decoder over an in-memory byte slice.
+///
+/// Frames borrow directly from the slice rather than from the decoder, so
+/// they outlive the decoder borrow and no frame data is copied.
+pub struct SliceDecoder<'a> {
+    data: &'a [u8],
+    state: SliceState,
+}
+
+impl<'a> SliceDecoder<'a> {
+    /// Create a new decoder instance.
+    pub fn new(data: &'a [u8]) -> Result<SliceDecoder<'a>, Error> {
+        Ok(SliceDecoder {
+            data,
+            state: SliceState::new(data)?,
+        })
+    }
+
+    /// Iterate over frames. End of input is indicated by `Error::EOF`.
+    ///
+    /// `Error::EOF` is returned only if the data ends at a frame boundary,
+    /// otherwise one of the truncation errors is returned.
+    pub fn read_frame(&mut self) -> Result<Frame<'a>, Error> {
+        self.state.read_frame(self.data)
+    }
+
+    /// Return the given frame without changing the position of `read_frame`.
+    /// `Error::BadInput` is returned if there is no such frame.
+    pub fn get_frame(&mut self, frame_index: usize) -> Result<Frame<'a>, Error> {
+        self.state.get_frame(self.data, frame_index)
+    }
+
+    /// Return the number of complete frames in the data.
+    ///
+    /// The data is scanned once to find the frame headers. If they all are
+    /// bare (`FRAME` without parameters), frames are located by their size,
+    /// otherwise an index of frame offsets is kept.
+    pub fn frame_count(&mut self) -> Result<usize, Error> {
+        self.state.frame_count(self.data)
+    }
+
+    /// Seek to the given frame, so the next `read_frame` call returns it.
+    /// Seeking to `frame_count` is allowed, reading then returns
+    /// `Error::EOF`. Seeking past that returns `Error::BadInput`.
+    pub fn seek_to_frame(&mut self, frame_index: usize) -> Result<(), Error> {
+        self.state.seek_to_frame(self.data, frame_index)
+    }
+
+    header_getters!(state.header);
+}
+
+// Decoding state over in-memory data, shared by `SliceDecoder` and
+// `MmapDecoder`. The data is passed to every call, so the owner decides how
+// long frames borrow it.
+struct SliceState {
+    header: Header,
+    // Offset and index of the next frame.
+    offset: usize,
+    frame_index: usize,
+    // Offset of the first frame.
+    data_offset: usize,
+    layout: FrameLayout,
+}
+
+impl SliceState {
+    fn new(data: &[u8]) -> Result<SliceState, Error> {
+        let (header, data_offset) = parse_header_slice(data)?;
+        Ok(SliceState {
+            header,
+            offset: data_offset,
+            frame_index: 0,
+            data_offset,
+            layout: FrameLayout::Unknown,
+        })
+    }
+
+    fn read_frame<'d>(&mut self, data: &'d [u8]) -> Result<Frame<'d>, Error> {
+        let (frame, end) = self.frame_at(data, self.offset, self.frame_index)?;
+        self.offset = end;
+        self.frame_index += 1;
+        Ok(frame)
+    }
+
+    fn get_frame<'d>(&mut self, data: &'d [u8], frame_index: usize) -> Result<Frame<'d>, Error> {
+        if frame_index >= self.frame_count(data)? {
+            return Err(Error::BadInput);
+        }
+        let offset = self.frame_offset(data, frame_index)?;
+        Ok(self.frame_at(data, offset, frame_index)?.0)
+    }
+
+    fn frame_count(&mut self, data: &[u8]) -> Result<usize, Error> {
+        self.detect_layout(data)?;
+        match self.layout {
+            FrameLayout::Fixed(count) => Ok(count),
+            FrameLayout::Indexed(ref index) => Ok(index.len()),
+            FrameLayout::Unknown => unreachable!(),
+        }
+    }
+
+    fn seek_to_frame(&mut self, data: &[u8], frame_index: usize) -> Result<(), Error> {
+        self.offset = self.frame_offset(data, frame_index)?;
+        self.frame_index = frame_index;
+        Ok(())
+    }
+
+    // Return frame at the given offset and offset of the next frame.
+    fn frame_at<'d>(
+        &self,
+        data: &'d [u8],
+        offset: usize,
+        frame_index: usize,
+    ) -> Result<(Frame<'d>, usize), Error> {
+        let (raw_params, range) = parse_frame_slice(data, offset, frame_index, &self.header)?;
+        let end = range.end;
+        let planes = self.header.split_planes(&data[range]);
+        Ok((Frame::new_with_alpha(planes, raw_params), end))
+    }
+
+    // Return offset of the given frame, or the data end for `frame_count`.
+    fn frame_offset(&mut self, data: &[u8], frame_index: usize) -> Result<usize, Error> {
+        self.detect_layout(data)?;
+        match self.layout {
+            FrameLayout::Fixed(count) if frame_index <= count => {
+                Ok(self.data_offset + frame_index * self.header.frame_stride())
+            }
+            FrameLayout::Indexed(ref index) if frame_index <= index.len() => {
+                match index.get(frame_index) {
+                    Some(entry) => Ok(entry.offset as usize),
+                    None => Ok(index.stream_len as usize),
+                }
+            }
+            _ => Err(Error::BadInput),
+        }
+    }
+
+    fn detect_layout(&mut self, data: &[u8]) -> Result<(), Error> {
+        if let FrameLayout::Unknown = self.layout {
+            self.layout = self.scan_layout(data)?;
+        }
+        Ok(())
+    }
+
+    fn scan_layout(&self, data: &[u8]) -> Result<FrameLayout, Error> {
+        let mut entries = Vec::new();
+        let mut offset = self.data_offset;
+        loop {
+            match parse_frame_slice(data, offset, entries.len(), &self.header) {
+                Ok((raw_params, range)) => {
+                    entries.push(FrameIndexEntry {
+                        offset: offset as u64,
+                        raw_params,
+                    });
+                    offset = range.end;
+                }
+                // Skip incomplete last frame.
+                Err(Error::EOF)
+                | Err(Error::TruncatedFrameHeader { .. })
+                | Err(Error::TruncatedFrame { .. }) => break,
+                Err(err) => return Err(err),
+            }
+        }
+        // Frames with bare headers all have the same size.
+        if entries.iter().all(|entry| entry.raw_params.is_none()) {
+            return Ok(FrameLayout::Fixed(entries.len()));
+        }
+        Ok(FrameLayout::Indexed(FrameIndex {
+            header_hash: self.header.hash(),
+            data_offset: self.data_offset as u64,
+            frame_size: self.header.frame_size as u64,
+            stream_len: data.len() as u64,
+            entries,
+        }))
+    }
+}
+
+/// Single frame of the `FrameIndex`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameIndexEntry {
+    /// Stream offset of the frame header.
+    pub offset: u64,
+    /// Frame raw parameters if any.
+    pub raw_params: Option<Vec<u8>>,
+}
+
+/// Index of frames in a y4m stream: byte offset and raw parameters of every
+/// frame. Allows O(1) seeking even if frame headers have variable length.
+///
+/// Can be stored in a small sidecar file with `write_to` and loaded back
+/// with `read_from`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameIndex {
+    header_hash: u64,
+    data_offset: u64,
+    frame_size: u64,
+    stream_len: u64,
+    entries: Vec<FrameIndexEntry>,
+}
+
+impl FrameIndex {
+    /// Return the number of indexed frames.
     #[inline]
-    pub fn get_colorspace(&self) -> Colorspace {
-        self.colorspace
+    pub fn len(&self) -> usize {
+        self.entries.len()
     }
-    /// Return file raw parameters.
+    /// Return whether the index has no frames.
     #[inline]
-    pub fn get_raw_params(&self) -> &[u8] {
-        &self.raw_params
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
     }
-    /// Return the bit depth per sample
+    /// Return the given frame entry.
     #[inline]
-    pub fn get_bit_depth(&self) -> usize {
-        self.colorspace.get_bit_depth()
+    pub fn get(&self, frame_index: usize) -> Option<&FrameIndexEntry> {
+        self.entries.get(frame_index)
     }
-    /// Return the number of bytes in a sample
+    /// Return all frame entries.
     #[inline]
-    pub fn get_bytes_per_sample(&self) -> usize {
-        self.colorspace.get_bytes_per_sample()
+    pub fn entries(&self) -> &[FrameIndexEntry] {
+        &self.entries
+    }
+
+    /// Serialize the index. All numbers are stored in little-endian, missing
+    /// frame parameters are stored as `u32::MAX` length.
+    #[cfg(feature = "std")]
+    pub fn write_to<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        writer.write_all(INDEX_MAGICK)?;
+        for value in [
+            self.header_hash,
+            self.data_offset,
+            self.frame_size,
+            self.stream_len,
+            self.entries.len() as u64,
+        ] {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+        for entry in self.entries.iter() {
+            writer.write_all(&entry.offset.to_le_bytes())?;
+            match entry.raw_params {
+                Some(ref raw_params) => {
+                    writer.write_all(&(raw_params.len() as u32).to_le_bytes())?;
+                    writer.write_all(raw_params)?;
+                }
+                None => writer.write_all(&u32::MAX.to_le_bytes())?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Deserialize the index written by `write_to`.
+    #[cfg(feature = "std")]
+    pub fn read_from<R: Read>(mut reader: R) -> Result<FrameIndex, Error> {
+        fn read_u64<R: Read>(reader: &mut R) -> Result<u64, Error> {
+            let mut buf = [0; 8];
+            reader.read_exact(&mut buf)?;
+            Ok(u64::from_le_bytes(buf))
+        }
+        let mut magick = [0; INDEX_MAGICK.len()];
+        reader.read_exact(&mut magick)?;
+        if magick != INDEX_MAGICK {
+            parse_error!(ParseErrorKind::General)
+        }
+        let header_hash = read_u64(&mut reader)?;
+        let data_offset = read_u64(&mut reader)?;
+        let frame_size = read_u64(&mut reader)?;
+        let stream_len = read_u64(&mut reader)?;
+        let count = read_u64(&mut reader)?;
+        // Don't trust the count for preallocation, file might be corrupted.
+        let mut entries = Vec::new();
+        for _ in 0..count {
+            let offset = read_u64(&mut reader)?;
+            let mut len = [0; 4];
+            reader.read_exact(&mut len)?;
+            let len = u32::from_le_bytes(len);
+            let raw_params = if len == u32::MAX {
+                None
+            } else if len as usize > MAX_PARAMS_SIZE {
+                parse_error!(ParseErrorKind::General)
+            } else {
+                let mut raw_params = vec![0; len as usize];
+                reader.read_exact(&mut raw_params)?;
+                Some(raw_params)
+            };
+            entries.push(FrameIndexEntry { offset, raw_params });
+        }
+        Ok(FrameIndex {
+            header_hash,
+            data_offset,
+            frame_size,
+            stream_len,
+            entries,
+        })
+    }
+}
+
+/// Lending iterator over decoded frames, see `Decoder::frames`.
+///
+/// Ends at the frame boundary, other errors (including truncated frames) are
+/// yielded once and stop the iteration.
+#[cfg(feature = "std")]
+pub struct Frames<'d, R: Read> {
+    decoder: &'d mut Decoder<R>,
+    done: bool,
+}
+
+#[cfg(feature = "std")]
+impl<R: Read> Frames<'_, R> {
+    /// Return next frame or `None` at the end of stream. Frame borrows the
+    /// decoder buffer so it can't outlive the next call, hence `Iterator`
+    /// can't be implemented.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<Result<Frame<'_>, Error>> {
+        if self.done {
+            return None;
+        }
+        match self.decoder.read_frame() {
+            Ok(frame) => Some(Ok(frame)),
+            Err(Error::EOF) => {
+                self.done = true;
+                None
+            }
+            Err(err) => {
+                self.done = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+/// Iterator over owned frames, see `Decoder::owned_frames`.
+///
+/// Ends at the frame boundary, other errors (including truncated frames) are
+/// yielded once and stop the iteration.
+#[cfg(feature = "std")]
+pub struct OwnedFrames<'d, R: Read> {
+    decoder: &'d mut Decoder<R>,
+    done: bool,
+}
+
+#[cfg(feature = "std")]
+impl<R: Read> Iterator for OwnedFrames<'_, R> {
+    type Item = Result<FrameBuf, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.decoder.read_frame() {
+            Ok(frame) => Some(Ok(frame.to_owned())),
+            Err(Error::EOF) => {
+                self.done = true;
+                None
+            }
+            Err(err) => {
+                self.done = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+/// Presentation of a frame in mixed-mode stream (first character of the
+/// frame `I` tag).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FramePresentation {
+    /// Top field first (`t`).
+    TopFieldFirst,
+    /// Top field first, repeat top field (`T`).
+    TopFieldFirstRepeat,
+    /// Bottom field first (`b`).
+    BottomFieldFirst,
+    /// Bottom field first, repeat bottom field (`B`).
+    BottomFieldFirstRepeat,
+    /// Single progressive frame (`1`).
+    SingleProgressive,
+    /// Double progressive frame, i.e. repeat it once (`2`).
+    DoubleProgressive,
+    /// Triple progressive frame, i.e. repeat it twice (`3`).
+    TripleProgressive,
+}
+
+/// Sampling of a frame (second and third characters of the frame `I` tag).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameSampling {
+    /// Progressive (`p`).
+    Progressive,
+    /// Interlaced (`i`).
+    Interlaced,
+    /// Unknown (`?`).
+    Unknown,
+}
+
+impl FrameSampling {
+    fn parse(value: u8) -> Result<FrameSampling, Error> {
+        match value {
+            b'p' => Ok(FrameSampling::Progressive),
+            b'i' => Ok(FrameSampling::Interlaced),
+            b'?' => Ok(FrameSampling::Unknown),
+            _ => parse_error!(ParseErrorKind::General),
+        }
+    }
+
+    fn as_byte(self) -> u8 {
+        match self {
+            FrameSampling::Progressive => b'p',
+            FrameSampling::Interlaced => b'i',
+            FrameSampling::Unknown => b'?',
+        }
+    }
+}
+
+/// Per-frame interlacing flags (frame `Ixyz` tag) used in mixed-mode streams.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameInterlacing {
+    /// How the frame should be presented.
+    pub presentation: FramePresentation,
+    /// Temporal sampling of the frame.
+    pub temporal: FrameSampling,
+    /// Sampling of the chroma planes.
+    pub subsampling: FrameSampling,
+}
+
+impl FrameInterlacing {
+    /// Parse per-frame interlacing flags from the `I` tag value.
+    pub fn parse(value: &[u8]) -> Result<FrameInterlacing, Error> {
+        if value.len() != 3 {
+            parse_error!(ParseErrorKind::General)
+        }
+        let presentation = match value[0] {
+            b't' => FramePresentation::TopFieldFirst,
+            b'T' => FramePresentation::TopFieldFirstRepeat,
+            b'b' => FramePresentation::BottomFieldFirst,
+            b'B' => FramePresentation::BottomFieldFirstRepeat,
+            b'1' => FramePresentation::SingleProgressive,
+            b'2' => FramePresentation::DoubleProgressive,
+            b'3' => FramePresentation::TripleProgressive,
+            _ => parse_error!(ParseErrorKind::General),
+        };
+        Ok(FrameInterlacing {
+            presentation,
+            temporal: FrameSampling::parse(value[1])?,
+            subsampling: FrameSampling::parse(value[2])?,
+        })
+    }
+
+    fn to_bytes(self) -> [u8; 3] {
+        let presentation = match self.presentation {
+            FramePresentation::TopFieldFirst => b't',
+            FramePresentation::TopFieldFirstRepeat => b'T',
+            FramePresentation::BottomFieldFirst => b'b',
+            FramePresentation::BottomFieldFirstRepeat => b'B',
+            FramePresentation::SingleProgressive => b'1',
+            FramePresentation::DoubleProgressive => b'2',
+            FramePresentation::TripleProgressive => b'3',
+        };
+        [
+            presentation,
+            self.temporal.as_byte(),
+            self.subsampling.as_byte(),
+        ]
+    }
+}
+
+/// Parsed frame parameters.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FrameParams {
+    /// Per-frame interlacing flags, if present.
+    pub interlacing: Option<FrameInterlacing>,
+    /// Per-frame vendor extensions, in stream order.
+    pub vendor_extensions: Vec<VendorExtensionString>,
+}
+
+impl FrameParams {
+    /// Create empty frame parameters.
+    pub fn new() -> FrameParams {
+        FrameParams::default()
+    }
+
+    /// Parse frame parameters from raw bytes (as returned by
+    /// `Frame::get_raw_params`). Unknown tags are ignored.
+    pub fn parse(raw_params: &[u8]) -> Result<FrameParams, Error> {
+        let mut params = FrameParams::new();
+        for param in raw_params.split(|&b| b == FIELD_SEP) {
+            if param.is_empty() {
+                continue;
+            }
+            let (name, value) = (param[0], &param[1..]);
+            match name {
+                b'I' => {
+                    params.interlacing = Some(
+                        FrameInterlacing::parse(value)
+                            .map_err(|err| err.with_parse_context(|e| e.with_tag(name, value)))?,
+                    )
+                }
+                b'X' => params
+                    .vendor_extensions
+                    .push(VendorExtensionString(value.to_owned())),
+                _ => {}
+            }
+        }
+        Ok(params)
+    }
+
+    /// Serialize frame parameters into raw bytes. `None` is returned if there
+    /// are no parameters to write.
+    pub fn to_raw_params(&self) -> Option<Vec<u8>> {
+        let mut raw_params = Vec::new();
+        if let Some(interlacing) = self.interlacing {
+            raw_params.push(b'I');
+            raw_params.extend_from_slice(&interlacing.to_bytes());
+        }
+        for x_option in self.vendor_extensions.iter() {
+            if !raw_params.is_empty() {
+                raw_params.push(FIELD_SEP);
+            }
+            raw_params.push(b'X');
+            raw_params.extend_from_slice(x_option.value());
+        }
+        if raw_params.is_empty() {
+            None
+        } else {
+            Some(raw_params)
+        }
     }
 }
 
 /// A single frame.
 #[derive(Debug)]
 pub struct Frame<'f> {
-    planes: [&'f [u8]; 3],
+    planes: [&'f [u8]; 4],
     raw_params: Option<Vec<u8>>,
 }
 
@@ -511,27 +2325,56 @@ impl<'f> Frame<'f> {
     /// Create a new frame with optional parameters.
     /// No heap allocations are made.
     pub fn new(planes: [&'f [u8]; 3], raw_params: Option<Vec<u8>>) -> Frame<'f> {
+        Frame::new_with_alpha([planes[0], planes[1], planes[2], &[]], raw_params)
+    }
+
+    /// Create a new frame with alpha plane and optional parameters.
+    /// No heap allocations are made.
+    pub fn new_with_alpha(planes: [&'f [u8]; 4], raw_params: Option<Vec<u8>>) -> Frame<'f> {
         Frame { planes, raw_params }
     }
 
+    /// Copy frame data into a new owned frame.
+    pub fn to_owned(&self) -> FrameBuf {
+        let mut plane_lens = [0; 4];
+        for (len, plane) in plane_lens.iter_mut().zip(self.planes.iter()) {
+            *len = plane.len();
+        }
+        FrameBuf {
+            data: self.planes.concat(),
+            plane_lens,
+            raw_params: self.raw_params.clone(),
+        }
+    }
+
+    /// Replace frame parameters with the given ones.
+    pub fn with_params(mut self, params: &FrameParams) -> Frame<'f> {
+        self.raw_params = params.to_raw_params();
+        self
+    }
+
     /// Create a new frame from data in 16-bit format.
+    ///
+    /// Samples are reinterpreted in host byte order, so the data is only
+    /// correct on little-endian hosts.
+    #[deprecated(note = "produces host-endian data, use `FrameBuf::from_u16` instead")]
     pub fn from_u16(planes: [&'f [u16]; 3], raw_params: Option<Vec<u8>>) -> Frame<'f> {
         Frame::new(
             [
                 unsafe {
-                    std::slice::from_raw_parts::<u8>(
+                    core::slice::from_raw_parts::<u8>(
                         planes[0].as_ptr() as *const u8,
                         planes[0].len() * 2,
                     )
                 },
                 unsafe {
-                    std::slice::from_raw_parts::<u8>(
+                    core::slice::from_raw_parts::<u8>(
                         planes[1].as_ptr() as *const u8,
                         planes[1].len() * 2,
                     )
                 },
                 unsafe {
-                    std::slice::from_raw_parts::<u8>(
+                    core::slice::from_raw_parts::<u8>(
                         planes[2].as_ptr() as *const u8,
                         planes[2].len() * 2,
                     )
@@ -551,20 +2394,358 @@ impl<'f> Frame<'f> {
     pub fn get_u_plane(&self) -> &[u8] {
         self.planes[1]
     }
-    /// Return V (third) plane. Empty in case of grayscale.
-    #[inline]
-    pub fn get_v_plane(&self) -> &[u8] {
-        self.planes[2]
+    /// Return V (third) plane. Empty in case of grayscale.
+    #[inline]
+    pub fn get_v_plane(&self) -> &[u8] {
+        self.planes[2]
+    }
+    /// Return A (fourth) plane. Empty unless colorspace has alpha.
+    #[inline]
+    pub fn get_a_plane(&self) -> &[u8] {
+        self.planes[3]
+    }
+    /// Return Y plane samples of a high bit depth frame (2 bytes per sample).
+    #[inline]
+    pub fn get_y_plane_u16(&self) -> Samples16<'f> {
+        Samples16::new(self.planes[0])
+    }
+    /// Return U plane samples of a high bit depth frame (2 bytes per sample).
+    #[inline]
+    pub fn get_u_plane_u16(&self) -> Samples16<'f> {
+        Samples16::new(self.planes[1])
+    }
+    /// Return V plane samples of a high bit depth frame (2 bytes per sample).
+    #[inline]
+    pub fn get_v_plane_u16(&self) -> Samples16<'f> {
+        Samples16::new(self.planes[2])
+    }
+    /// Return A plane samples of a high bit depth frame (2 bytes per sample).
+    #[inline]
+    pub fn get_a_plane_u16(&self) -> Samples16<'f> {
+        Samples16::new(self.planes[3])
+    }
+    /// Return views of Y, U, V and A planes of a frame with the given
+    /// dimensions and colorspace, e.g. as returned by the decoder.
+    ///
+    /// `Error::BadInput` is returned if plane sizes don't match.
+    pub fn get_planes(
+        &self,
+        width: usize,
+        height: usize,
+        colorspace: Colorspace,
+    ) -> Result<[Plane<'f>; 4], Error> {
+        let bytes_per_sample = colorspace.get_bytes_per_sample();
+        let dims = get_plane_dimensions(width, height, colorspace);
+        let plane = |i: usize| {
+            let (width, height) = dims[i];
+            let stride = width * bytes_per_sample;
+            if self.planes[i].len() != stride * height {
+                return Err(Error::BadInput);
+            }
+            Ok(Plane {
+                data: self.planes[i],
+                width,
+                height,
+                stride,
+                bytes_per_sample,
+            })
+        };
+        Ok([plane(0)?, plane(1)?, plane(2)?, plane(3)?])
+    }
+    /// Return samples of all planes in Y, U, V, A order.
+    pub fn planes_u16(&self) -> [Samples16<'f>; 4] {
+        [
+            self.get_y_plane_u16(),
+            self.get_u_plane_u16(),
+            self.get_v_plane_u16(),
+            self.get_a_plane_u16(),
+        ]
+    }
+    /// Return frame raw parameters if any.
+    #[inline]
+    pub fn get_raw_params(&self) -> Option<&[u8]> {
+        self.raw_params.as_ref().map(|v| &v[..])
+    }
+    /// Return parsed frame parameters. Empty if frame has no parameters.
+    pub fn get_params(&self) -> Result<FrameParams, Error> {
+        match self.raw_params {
+            Some(ref raw_params) => FrameParams::parse(raw_params),
+            None => Ok(FrameParams::new()),
+        }
+    }
+}
+
+/// View of a single plane with its dimensions.
+///
+/// Rows start `stride` bytes apart, so planes stored in larger padded buffers
+/// can be described without copying. Samples take `bytes_per_sample` bytes,
+/// 16-bit ones are little-endian.
+#[derive(Debug, Clone, Copy)]
+pub struct Plane<'a> {
+    data: &'a [u8],
+    width: usize,
+    height: usize,
+    stride: usize,
+    bytes_per_sample: usize,
+}
+
+impl<'a> Plane<'a> {
+    /// Create a new plane view. Width and height are given in samples,
+    /// stride in bytes.
+    ///
+    /// `Error::BadInput` is returned if bytes per sample is not 1 or 2, stride
+    /// is shorter than a row or data is too short.
+    pub fn new(
+        data: &'a [u8],
+        width: usize,
+        height: usize,
+        stride: usize,
+        bytes_per_sample: usize,
+    ) -> Result<Plane<'a>, Error> {
+        if bytes_per_sample != 1 && bytes_per_sample != 2 {
+            return Err(Error::BadInput);
+        }
+        // Length of the data covering all rows, `None` on overflow.
+        let row_len = width.checked_mul(bytes_per_sample);
+        let len = match (row_len, height.checked_sub(1)) {
+            (Some(row_len), Some(last)) => last
+                .checked_mul(stride)
+                .and_then(|start| start.checked_add(row_len)),
+            (Some(_), None) => Some(0),
+            (None, _) => None,
+        };
+        match (row_len, len) {
+            (Some(row_len), Some(len)) if stride >= row_len && data.len() >= len => {}
+            _ => return Err(Error::BadInput),
+        }
+        Ok(Plane {
+            data,
+            width,
+            height,
+            stride,
+            bytes_per_sample,
+        })
+    }
+
+    /// Return plane width in samples.
+    #[inline]
+    pub fn width(&self) -> usize {
+        self.width
+    }
+    /// Return plane height in samples.
+    #[inline]
+    pub fn height(&self) -> usize {
+        self.height
+    }
+    /// Return distance between rows in bytes.
+    #[inline]
+    pub fn stride(&self) -> usize {
+        self.stride
+    }
+    /// Return the number of bytes in a sample.
+    #[inline]
+    pub fn bytes_per_sample(&self) -> usize {
+        self.bytes_per_sample
+    }
+
+    /// Return the given row without padding.
+    pub fn row(&self, y: usize) -> Option<&'a [u8]> {
+        if y >= self.height {
+            return None;
+        }
+        let start = y * self.stride;
+        Some(&self.data[start..start + self.width * self.bytes_per_sample])
+    }
+
+    /// Iterate over rows without padding.
+    pub fn rows(&self) -> impl DoubleEndedIterator<Item = &'a [u8]> + ExactSizeIterator {
+        let plane = *self;
+        (0..self.height).map(move |y| plane.row(y).unwrap())
+    }
+
+    /// Return the sample at the given position.
+    pub fn get(&self, x: usize, y: usize) -> Option<u16> {
+        if x >= self.width {
+            return None;
+        }
+        let row = self.row(y)?;
+        Some(match self.bytes_per_sample {
+            1 => row[x] as u16,
+            _ => u16::from_le_bytes([row[2 * x], row[2 * x + 1]]),
+        })
+    }
+}
+
+/// Iterator over 16-bit samples of a plane.
+///
+/// y4m stores high bit depth samples in little-endian, they are converted to
+/// the host byte order. Trailing odd byte, if any, is ignored.
+#[derive(Debug, Clone)]
+pub struct Samples16<'a> {
+    chunks: core::slice::ChunksExact<'a, u8>,
+}
+
+impl<'a> Samples16<'a> {
+    fn new(plane: &'a [u8]) -> Samples16<'a> {
+        Samples16 {
+            chunks: plane.chunks_exact(2),
+        }
+    }
+}
+
+impl Iterator for Samples16<'_> {
+    type Item = u16;
+
+    #[inline]
+    fn next(&mut self) -> Option<u16> {
+        self.chunks
+            .next()
+            .map(|sample| u16::from_le_bytes([sample[0], sample[1]]))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.chunks.size_hint()
+    }
+}
+
+impl DoubleEndedIterator for Samples16<'_> {
+    #[inline]
+    fn next_back(&mut self) -> Option<u16> {
+        self.chunks
+            .next_back()
+            .map(|sample| u16::from_le_bytes([sample[0], sample[1]]))
+    }
+}
+
+impl ExactSizeIterator for Samples16<'_> {}
+
+// Append a new tag, keeping trailing separators of the original header at
+// the end.
+fn push_tag(tags: &mut Vec<Vec<u8>>, tag: Vec<u8>) {
+    let pos = tags
+        .iter()
+        .rposition(|t| !t.is_empty())
+        .map_or(0, |p| p + 1);
+    tags.insert(pos, tag);
+}
+
+/// A single frame which owns its data.
+///
+/// Planes are stored in a single contiguous buffer which can be reused with
+/// `Decoder::read_frame_buf` and `FramePool`.
+#[derive(Debug, Clone, Default)]
+pub struct FrameBuf {
+    data: Vec<u8>,
+    plane_lens: [usize; 4],
+    raw_params: Option<Vec<u8>>,
+}
+
+impl FrameBuf {
+    /// Create an empty frame buffer.
+    pub fn new() -> FrameBuf {
+        FrameBuf::default()
+    }
+
+    /// Create a new frame from 16-bit samples. Samples are stored in
+    /// little-endian regardless of the host byte order.
+    pub fn from_u16(planes: [&[u16]; 3], raw_params: Option<Vec<u8>>) -> FrameBuf {
+        FrameBuf::from_u16_with_alpha([planes[0], planes[1], planes[2], &[]], raw_params)
+    }
+
+    /// Create a new frame with alpha plane from 16-bit samples. See
+    /// `from_u16`.
+    pub fn from_u16_with_alpha(planes: [&[u16]; 4], raw_params: Option<Vec<u8>>) -> FrameBuf {
+        let mut plane_lens = [0; 4];
+        for (len, plane) in plane_lens.iter_mut().zip(planes.iter()) {
+            *len = plane.len() * 2;
+        }
+        let data = planes
+            .iter()
+            .flat_map(|plane| plane.iter())
+            .flat_map(|sample| sample.to_le_bytes())
+            .collect();
+        FrameBuf {
+            data,
+            plane_lens,
+            raw_params,
+        }
+    }
+
+    // Resize buffer for the given plane sizes, keeping the allocation.
+    #[cfg(feature = "std")]
+    fn reset(&mut self, plane_lens: [usize; 4], raw_params: Option<Vec<u8>>) {
+        self.data.resize(plane_lens.iter().sum(), 0);
+        self.plane_lens = plane_lens;
+        self.raw_params = raw_params;
     }
+
     /// Return frame raw parameters if any.
     #[inline]
     pub fn get_raw_params(&self) -> Option<&[u8]> {
         self.raw_params.as_ref().map(|v| &v[..])
     }
+
+    /// Borrow as a `Frame`.
+    pub fn as_frame(&self) -> Frame<'_> {
+        let (y_end, u_end) = (self.plane_lens[0], self.plane_lens[0] + self.plane_lens[1]);
+        let v_end = u_end + self.plane_lens[2];
+        Frame::new_with_alpha(
+            [
+                &self.data[0..y_end],
+                &self.data[y_end..u_end],
+                &self.data[u_end..v_end],
+                &self.data[v_end..v_end + self.plane_lens[3]],
+            ],
+            self.raw_params.clone(),
+        )
+    }
+}
+
+/// Pool of frame buffers to reuse allocations between frames.
+///
+/// Take a buffer with `get`, fill it with `Decoder::read_frame_buf` and
+/// return it with `recycle` once the frame is no longer needed.
+#[derive(Debug, Default)]
+pub struct FramePool {
+    free: Vec<Vec<u8>>,
+}
+
+impl FramePool {
+    /// Create an empty pool.
+    pub fn new() -> FramePool {
+        FramePool::default()
+    }
+
+    /// Take a frame buffer from the pool or create a new one if the pool is
+    /// empty. The buffer has no planes until it is filled.
+    pub fn get(&mut self) -> FrameBuf {
+        FrameBuf {
+            data: self.free.pop().unwrap_or_default(),
+            plane_lens: [0; 4],
+            raw_params: None,
+        }
+    }
+
+    /// Return a frame buffer to the pool. The data is kept as is to avoid
+    /// clearing it on reuse, `Decoder::read_frame_buf` overwrites stale
+    /// contents of the previous frame.
+    pub fn recycle(&mut self, frame: FrameBuf) {
+        self.free.push(frame.data);
+    }
+
+    /// Return number of buffers available in the pool.
+    pub fn len(&self) -> usize {
+        self.free.len()
+    }
+
+    /// Return whether the pool has no available buffers.
+    pub fn is_empty(&self) -> bool {
+        self.free.is_empty()
+    }
 }
 
 /// Encoder builder. Allows to set y4m file parameters using builder pattern.
-// TODO(Kagami): Accept all known tags and raw params.
 #[derive(Debug)]
 pub struct EncoderBuilder {
     width: usize,
@@ -572,7 +2753,13 @@ pub struct EncoderBuilder {
     framerate: Ratio,
     pixel_aspect: Ratio,
     colorspace: Colorspace,
+    interlacing: Interlacing,
+    color_range: Option<ColorRange>,
+    chroma_siting: Option<ChromaSiting>,
+    color_description: ColorDescription,
     vendor_extensions: Vec<Vec<u8>>,
+    // Original header tags in stream order if created from decoder.
+    tags: Option<Vec<Vec<u8>>>,
 }
 
 impl EncoderBuilder {
@@ -584,88 +2771,371 @@ impl EncoderBuilder {
             framerate,
             pixel_aspect: Ratio::new(1, 1),
             colorspace: Colorspace::C420,
+            interlacing: Interlacing::Progressive,
+            color_range: None,
+            chroma_siting: None,
+            color_description: ColorDescription::default(),
+            vendor_extensions: vec![],
+            tags: None,
+        }
+    }
+
+    /// Create a new encoder builder with all header tags copied from the
+    /// decoder, including unknown ones and vendor extensions.
+    ///
+    /// Tags are written in their original order, so unless some parameter is
+    /// changed the resulting header is byte-identical to the decoded one.
+    /// Changed parameters replace the corresponding tag in place, new ones
+    /// are appended to the end of the header.
+    #[cfg(feature = "std")]
+    pub fn from_decoder<R: Read>(decoder: &Decoder<R>) -> EncoderBuilder {
+        EncoderBuilder::from_header(decoder.header())
+    }
+
+    /// Create a new encoder builder preserving all parameters of the parsed
+    /// file header. See `from_decoder`.
+    pub fn from_header(header: &Header) -> EncoderBuilder {
+        let tags = header
+            .get_raw_params()
+            .split(|&b| b == FIELD_SEP)
+            .map(|tag| tag.to_owned())
+            .collect();
+        EncoderBuilder {
+            width: header.get_width(),
+            height: header.get_height(),
+            framerate: header.get_framerate(),
+            pixel_aspect: header.get_pixel_aspect(),
+            colorspace: header.get_colorspace(),
+            interlacing: header.get_interlacing(),
+            color_range: header.get_color_range(),
+            chroma_siting: header.get_chroma_siting(),
+            color_description: header.get_color_description(),
             vendor_extensions: vec![],
+            tags: Some(tags),
+        }
+    }
+
+    // Replace the first tag starting with `prefix` or append a new one. No-op
+    // unless builder preserves original tags.
+    fn set_tag(&mut self, prefix: &[u8], tag: Vec<u8>) {
+        if let Some(ref mut tags) = self.tags {
+            match tags.iter_mut().find(|t| t.starts_with(prefix)) {
+                Some(t) => *t = tag,
+                None => push_tag(tags, tag),
+            }
+        }
+    }
+
+    // Remove the first tag starting with `prefix`. No-op unless builder
+    // preserves original tags.
+    fn remove_tag(&mut self, prefix: &[u8]) {
+        if let Some(ref mut tags) = self.tags {
+            if let Some(pos) = tags.iter().position(|t| t.starts_with(prefix)) {
+                tags.remove(pos);
+            }
+        }
+    }
+
+    // Return chroma siting to be written, it's only meaningful for 4:2:0
+    // colorspaces.
+    fn written_chroma_siting(&self) -> Option<ChromaSiting> {
+        self.chroma_siting.filter(|_| self.colorspace.is_420())
+    }
+
+    // Bring the preserved `YSCSS` tag in sync with colorspace and siting.
+    fn update_chroma_siting_tag(&mut self) {
+        match self.written_chroma_siting() {
+            Some(chroma_siting) => {
+                self.set_tag(b"XYSCSS=", format!("XYSCSS={}", chroma_siting).into_bytes())
+            }
+            None => self.remove_tag(b"XYSCSS="),
         }
     }
 
-    /// Specify file colorspace.
+    /// Specify file colorspace. Chroma siting is replaced with the one implied
+    /// by the colorspace, or cleared if it implies none. Use
+    /// `with_chroma_siting` afterwards to set it explicitly.
     pub fn with_colorspace(mut self, colorspace: Colorspace) -> Self {
         self.colorspace = colorspace;
+        self.chroma_siting = colorspace.get_chroma_siting();
+        self.set_tag(b"C", format!("{:?}", colorspace).into_bytes());
+        self.update_chroma_siting_tag();
         self
     }
 
     /// Specify file pixel aspect.
     pub fn with_pixel_aspect(mut self, pixel_aspect: Ratio) -> Self {
         self.pixel_aspect = pixel_aspect;
+        self.set_tag(b"A", format!("A{}", pixel_aspect).into_bytes());
+        self
+    }
+
+    /// Specify file interlacing mode.
+    pub fn with_interlacing(mut self, interlacing: Interlacing) -> Self {
+        self.interlacing = interlacing;
+        self.set_tag(b"I", format!("I{}", interlacing).into_bytes());
+        self
+    }
+
+    /// Specify file color range. Written as `COLORRANGE` vendor extension.
+    pub fn with_color_range(mut self, color_range: ColorRange) -> Self {
+        self.color_range = Some(color_range);
+        self.set_tag(
+            b"XCOLORRANGE=",
+            format!("XCOLORRANGE={}", color_range).into_bytes(),
+        );
+        self
+    }
+
+    /// Specify file chroma siting. Written as `YSCSS` vendor extension and,
+    /// for 8-bit 4:2:0 colorspaces, as the corresponding colorspace. Not
+    /// written for colorspaces other than 4:2:0.
+    pub fn with_chroma_siting(mut self, chroma_siting: ChromaSiting) -> Self {
+        self.chroma_siting = Some(chroma_siting);
+        if let Some(colorspace) = self.colorspace.with_chroma_siting(chroma_siting) {
+            self.colorspace = colorspace;
+            self.set_tag(b"C", format!("{:?}", colorspace).into_bytes());
+        }
+        self.update_chroma_siting_tag();
+        self
+    }
+
+    /// Specify file color description. Written as `COLORPRIMARIES`,
+    /// `COLORTRC` and `COLORMATRIX` vendor extensions, missing values are
+    /// omitted.
+    pub fn with_color_description(mut self, color_description: ColorDescription) -> Self {
+        self.color_description = color_description;
+        match color_description.primaries {
+            Some(primaries) => self.set_tag(
+                b"XCOLORPRIMARIES=",
+                format!("XCOLORPRIMARIES={}", primaries).into_bytes(),
+            ),
+            None => self.remove_tag(b"XCOLORPRIMARIES="),
+        }
+        match color_description.transfer {
+            Some(transfer) => self.set_tag(
+                b"XCOLORTRC=",
+                format!("XCOLORTRC={}", transfer).into_bytes(),
+            ),
+            None => self.remove_tag(b"XCOLORTRC="),
+        }
+        match color_description.matrix {
+            Some(matrix) => self.set_tag(
+                b"XCOLORMATRIX=",
+                format!("XCOLORMATRIX={}", matrix).into_bytes(),
+            ),
+            None => self.remove_tag(b"XCOLORMATRIX="),
+        }
         self
     }
 
     /// Add vendor extension.
     pub fn append_vendor_extension(mut self, x_option: VendorExtensionString) -> Self {
-        self.vendor_extensions.push(x_option.0);
+        match self.tags {
+            Some(ref mut tags) => {
+                let mut tag = vec![b'X'];
+                tag.extend_from_slice(x_option.value());
+                push_tag(tags, tag);
+            }
+            None => self.vendor_extensions.push(x_option.0),
+        }
         self
     }
 
     /// Write header to the stream and create encoder instance.
+    #[cfg(feature = "std")]
     pub fn write_header<W: Write>(self, mut writer: W) -> Result<Encoder<W>, Error> {
-        // XXX(Kagami): Beware that FILE_MAGICK already contains space.
-        writer.write_all(FILE_MAGICK)?;
-        write!(
+        writer.write_all(&self.header_bytes())?;
+        Ok(Encoder {
             writer,
-            "W{} H{} F{}",
-            self.width, self.height, self.framerate
-        )?;
+            frame_writer: self.frame_writer(),
+        })
+    }
+
+    /// Serialize the file header including terminator. Together with
+    /// `frame_writer` allows encoding without IO.
+    pub fn header_bytes(&self) -> Vec<u8> {
+        // XXX(Kagami): Beware that FILE_MAGICK already contains space.
+        let mut header = FILE_MAGICK.to_vec();
+        if let Some(ref tags) = self.tags {
+            header.extend_from_slice(&tags.join(&FIELD_SEP));
+            header.push(TERMINATOR);
+            return header;
+        }
+        let mut tags = format!("W{} H{} F{}", self.width, self.height, self.framerate);
+        if self.interlacing != Interlacing::Progressive {
+            tags.push_str(&format!(" I{}", self.interlacing));
+        }
         if self.pixel_aspect.num != 1 || self.pixel_aspect.den != 1 {
-            write!(writer, " A{}", self.pixel_aspect)?;
+            tags.push_str(&format!(" A{}", self.pixel_aspect));
+        }
+        if let Some(chroma_siting) = self.written_chroma_siting() {
+            tags.push_str(&format!(" XYSCSS={}", chroma_siting));
+        }
+        if let Some(color_range) = self.color_range {
+            tags.push_str(&format!(" XCOLORRANGE={}", color_range));
         }
+        if let Some(primaries) = self.color_description.primaries {
+            tags.push_str(&format!(" XCOLORPRIMARIES={}", primaries));
+        }
+        if let Some(transfer) = self.color_description.transfer {
+            tags.push_str(&format!(" XCOLORTRC={}", transfer));
+        }
+        if let Some(matrix) = self.color_description.matrix {
+            tags.push_str(&format!(" XCOLORMATRIX={}", matrix));
+        }
+        header.extend_from_slice(tags.as_bytes());
         for x_option in self.vendor_extensions.iter() {
-            write!(writer, " X")?;
-            writer.write_all(x_option)?;
+            header.extend_from_slice(b" X");
+            header.extend_from_slice(x_option);
         }
-        write!(writer, " {:?}", self.colorspace)?;
-        writer.write_all(&[TERMINATOR])?;
-        let (y_len, u_len, v_len) = get_plane_sizes(self.width, self.height, self.colorspace);
-        Ok(Encoder {
-            writer,
-            y_len,
-            u_len,
-            v_len,
-        })
+        header.extend_from_slice(format!(" {:?}", self.colorspace).as_bytes());
+        header.push(TERMINATOR);
+        header
+    }
+
+    /// Create a frame writer for the stream parameters.
+    pub fn frame_writer(&self) -> FrameWriter {
+        let (y_len, u_len, v_len, a_len) =
+            get_plane_sizes(self.width, self.height, self.colorspace);
+        FrameWriter {
+            plane_lens: [y_len, u_len, v_len, a_len],
+            plane_dims: get_plane_dimensions(self.width, self.height, self.colorspace),
+            bytes_per_sample: self.colorspace.get_bytes_per_sample(),
+        }
+    }
+}
+
+/// YUV4MPEG2 frame serializer which doesn't do any IO, created with
+/// `EncoderBuilder::frame_writer`.
+#[derive(Debug, Clone)]
+pub struct FrameWriter {
+    plane_lens: [usize; 4],
+    plane_dims: [(usize, usize); 4],
+    bytes_per_sample: usize,
+}
+
+impl FrameWriter {
+    /// Serialize the frame header including terminator. Frame planes must
+    /// follow it in Y, U, V, A order.
+    ///
+    /// `Error::BadInput` is returned if plane sizes don't match the stream
+    /// parameters.
+    pub fn frame_header(&self, frame: &Frame) -> Result<Vec<u8>, Error> {
+        if frame
+            .planes
+            .iter()
+            .map(|plane| plane.len())
+            .ne(self.plane_lens)
+        {
+            return Err(Error::BadInput);
+        }
+        Ok(FrameWriter::header_bytes(frame.get_raw_params()))
+    }
+
+    /// Serialize the frame header for the strided planes. Rows of the planes
+    /// must follow it, see `write_planes`.
+    ///
+    /// `Error::BadInput` is returned if plane dimensions don't match the
+    /// stream parameters.
+    pub fn planes_header(
+        &self,
+        planes: &[Plane],
+        raw_params: Option<&[u8]>,
+    ) -> Result<Vec<u8>, Error> {
+        if planes.len() < 3 || planes.len() > 4 {
+            return Err(Error::BadInput);
+        }
+        for (i, &dims) in self.plane_dims.iter().enumerate() {
+            let matches = match planes.get(i) {
+                Some(plane) => {
+                    (plane.width, plane.height) == dims
+                        && plane.bytes_per_sample == self.bytes_per_sample
+                }
+                None => dims == (0, 0),
+            };
+            if !matches {
+                return Err(Error::BadInput);
+            }
+        }
+        Ok(FrameWriter::header_bytes(raw_params))
+    }
+
+    /// Append the frame made of the strided planes to the buffer. Planes are
+    /// given in Y, U, V, A order, A can be omitted if colorspace has no alpha.
+    pub fn write_planes(
+        &self,
+        planes: &[Plane],
+        raw_params: Option<&[u8]>,
+        buf: &mut Vec<u8>,
+    ) -> Result<(), Error> {
+        buf.extend_from_slice(&self.planes_header(planes, raw_params)?);
+        for row in planes.iter().flat_map(|plane| plane.rows()) {
+            buf.extend_from_slice(row);
+        }
+        Ok(())
+    }
+
+    fn header_bytes(raw_params: Option<&[u8]>) -> Vec<u8> {
+        let mut header = FRAME_MAGICK.to_vec();
+        if let Some(params) = raw_params {
+            header.push(FIELD_SEP);
+            header.extend_from_slice(params);
+        }
+        header.push(TERMINATOR);
+        header
+    }
+
+    /// Append the serialized frame to the buffer.
+    pub fn write_frame(&self, frame: &Frame, buf: &mut Vec<u8>) -> Result<(), Error> {
+        buf.extend_from_slice(&self.frame_header(frame)?);
+        for plane in frame.planes.iter() {
+            buf.extend_from_slice(plane);
+        }
+        Ok(())
     }
 }
 
 /// YUV4MPEG2 encoder.
+#[cfg(feature = "std")]
 pub struct Encoder<W: Write> {
     writer: W,
-    y_len: usize,
-    u_len: usize,
-    v_len: usize,
+    frame_writer: FrameWriter,
 }
 
+#[cfg(feature = "std")]
 impl<W: Write> Encoder<W> {
     /// Write next frame to the stream.
     pub fn write_frame(&mut self, frame: &Frame) -> Result<(), Error> {
-        if frame.get_y_plane().len() != self.y_len
-            || frame.get_u_plane().len() != self.u_len
-            || frame.get_v_plane().len() != self.v_len
-        {
-            return Err(Error::BadInput);
+        let header = self.frame_writer.frame_header(frame)?;
+        self.writer.write_all(&header)?;
+        for plane in frame.planes.iter() {
+            self.writer.write_all(plane)?;
         }
-        self.writer.write_all(FRAME_MAGICK)?;
-        if let Some(params) = frame.get_raw_params() {
-            self.writer.write_all(&[FIELD_SEP])?;
-            self.writer.write_all(params)?;
+        Ok(())
+    }
+
+    /// Write next frame made of the strided planes to the stream. Planes are
+    /// given in Y, U, V, A order, A can be omitted if colorspace has no alpha.
+    ///
+    /// `Error::BadInput` is returned if plane dimensions don't match the
+    /// stream parameters.
+    pub fn write_planes(
+        &mut self,
+        planes: &[Plane],
+        raw_params: Option<&[u8]>,
+    ) -> Result<(), Error> {
+        let header = self.frame_writer.planes_header(planes, raw_params)?;
+        self.writer.write_all(&header)?;
+        for row in planes.iter().flat_map(|plane| plane.rows()) {
+            self.writer.write_all(row)?;
         }
-        self.writer.write_all(&[TERMINATOR])?;
-        self.writer.write_all(frame.get_y_plane())?;
-        self.writer.write_all(frame.get_u_plane())?;
-        self.writer.write_all(frame.get_v_plane())?;
         Ok(())
     }
 
     /// Flush the underlying writer.
     pub fn flush(&mut self) -> Result<(), Error> {
-        self.writer.flush().map_err(|e| Error::IoError(e))
+        self.writer.flush().map_err(Error::IoError)
     }
 
     /// Return the underlying writer.
@@ -675,6 +3145,7 @@ impl<W: Write> Encoder<W> {
 }
 
 /// Create a new decoder instance. Alias for `Decoder::new`.
+#[cfg(feature = "std")]
 pub fn decode<R: Read>(reader: R) -> Result<Decoder<R>, Error> {
     Decoder::new(reader)
 }
@@ -683,3 +3154,844 @@ pub fn decode<R: Read>(reader: R) -> Result<Decoder<R>, Error> {
 pub fn encode(width: usize, height: usize, framerate: Ratio) -> EncoderBuilder {
     EncoderBuilder::new(width, height, framerate)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "std")]
+    use std::io::Cursor;
+
+    const HEADER: &[u8] = b"YUV4MPEG2 W2 H2 F25:1 C420\n";
+
+    // Stream with the given frame headers, frame data is filled with the
+    // frame index.
+    fn stream(frame_headers: &[&[u8]]) -> Vec<u8> {
+        let mut data = HEADER.to_vec();
+        for (i, frame_header) in frame_headers.iter().enumerate() {
+            data.extend_from_slice(frame_header);
+            data.extend_from_slice(&[i as u8; 6]);
+        }
+        data
+    }
+
+    #[cfg(feature = "std")]
+    fn decoder(data: &[u8]) -> Decoder<Cursor<&[u8]>> {
+        Decoder::new(Cursor::new(data)).unwrap()
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn fixed_size_frames() {
+        let data = stream(&[b"FRAME\n", b"FRAME\n", b"FRAME\n"]);
+        let mut decoder = decoder(&data);
+        assert_eq!(decoder.frame_count().unwrap(), 3);
+        assert!(matches!(decoder.layout, FrameLayout::Fixed(3)));
+        decoder.seek_to_frame(2).unwrap();
+        assert_eq!(decoder.read_frame().unwrap().get_y_plane(), [2; 4]);
+        assert!(matches!(decoder.read_frame(), Err(Error::EOF)));
+        decoder.seek_to_frame(1).unwrap();
+        assert_eq!(decoder.read_frame().unwrap().get_y_plane(), [1; 4]);
+        assert!(matches!(decoder.seek_to_frame(4), Err(Error::BadInput)));
+
+        let index = decoder.build_frame_index().unwrap();
+        let offsets: Vec<_> = index.entries().iter().map(|e| e.offset).collect();
+        assert_eq!(offsets, [27, 39, 51]);
+        assert!(index.entries().iter().all(|e| e.raw_params.is_none()));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn variable_size_frames() {
+        let data = stream(&[b"FRAME\n", b"FRAME Ixyz\n", b"FRAME\n"]);
+        let mut decoder = decoder(&data);
+        assert_eq!(decoder.frame_count().unwrap(), 3);
+        assert!(matches!(decoder.layout, FrameLayout::Indexed(_)));
+        decoder.seek_to_frame(1).unwrap();
+        let frame = decoder.read_frame().unwrap();
+        assert_eq!(frame.get_y_plane(), [1; 4]);
+        assert_eq!(frame.get_raw_params(), Some(&b"Ixyz"[..]));
+        decoder.seek_to_frame(2).unwrap();
+        assert_eq!(decoder.read_frame().unwrap().get_y_plane(), [2; 4]);
+        assert!(matches!(decoder.read_frame(), Err(Error::EOF)));
+    }
+
+    // Frame parameters whose lengths add up to a multiple of the frame
+    // stride must not be mistaken for fixed size frames.
+    #[cfg(feature = "std")]
+    #[test]
+    fn params_aligned_to_stride() {
+        let mut data = b"YUV4MPEG2 W1 H1 Cmono\n".to_vec();
+        for (i, frame_header) in [&b"FRAME\n"[..], b"FRAME Xabcde\n", b"FRAME\n"]
+            .iter()
+            .enumerate()
+        {
+            data.extend_from_slice(frame_header);
+            data.push(i as u8);
+        }
+        let mut decoder = decoder(&data);
+        assert_eq!(decoder.frame_count().unwrap(), 3);
+        decoder.seek_to_frame(2).unwrap();
+        assert_eq!(decoder.read_frame().unwrap().get_y_plane(), [2]);
+        decoder.seek_to_frame(1).unwrap();
+        let frame = decoder.read_frame().unwrap();
+        assert_eq!(frame.get_y_plane(), [1]);
+        assert_eq!(frame.get_raw_params(), Some(&b"Xabcde"[..]));
+    }
+
+    #[test]
+    fn slice_params_aligned_to_stride() {
+        let mut data = b"YUV4MPEG2 W1 H1 Cmono\n".to_vec();
+        for (i, frame_header) in [&b"FRAME\n"[..], b"FRAME Xabcde\n", b"FRAME\n"]
+            .iter()
+            .enumerate()
+        {
+            data.extend_from_slice(frame_header);
+            data.push(i as u8);
+        }
+        let mut decoder = SliceDecoder::new(&data).unwrap();
+        assert_eq!(decoder.frame_count().unwrap(), 3);
+        assert_eq!(decoder.get_frame(2).unwrap().get_y_plane(), [2]);
+        let frame = decoder.get_frame(1).unwrap();
+        assert_eq!(frame.get_y_plane(), [1]);
+        assert_eq!(frame.get_raw_params(), Some(&b"Xabcde"[..]));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn truncated_last_frame() {
+        for &tail in [&b"FRA"[..], b"FRAME\n", b"FRAME\n\x03\x03"].iter() {
+            for frame_headers in [
+                [&b"FRAME\n"[..], b"FRAME\n", b"FRAME\n"],
+                [b"FRAME\n", b"FRAME Ixyz\n", b"FRAME\n"],
+            ]
+            .iter()
+            {
+                let mut data = stream(frame_headers);
+                data.extend_from_slice(tail);
+                let mut decoder = decoder(&data);
+                assert_eq!(decoder.frame_count().unwrap(), 3);
+                decoder.seek_to_frame(2).unwrap();
+                assert_eq!(decoder.read_frame().unwrap().get_y_plane(), [2; 4]);
+                assert!(decoder.read_frame().is_err());
+
+                let mut decoder = SliceDecoder::new(&data).unwrap();
+                assert_eq!(decoder.frame_count().unwrap(), 3);
+                assert_eq!(decoder.get_frame(2).unwrap().get_y_plane(), [2; 4]);
+                assert!(matches!(decoder.get_frame(3), Err(Error::BadInput)));
+            }
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn seek_with_reader_not_at_start() {
+        for frame_headers in [
+            [&b"FRAME\n"[..], b"FRAME\n", b"FRAME\n"],
+            [b"FRAME\n", b"FRAME Ixyz\n", b"FRAME\n"],
+        ]
+        .iter()
+        {
+            let mut data = b"junk!".to_vec();
+            data.extend_from_slice(&stream(frame_headers));
+            let mut reader = Cursor::new(&data[..]);
+            reader.set_position(5);
+            let mut decoder = Decoder::new(reader).unwrap();
+            assert_eq!(decoder.frame_count().unwrap(), 3);
+            assert_eq!(decoder.read_frame().unwrap().get_y_plane(), [0; 4]);
+            decoder.seek_to_frame(2).unwrap();
+            assert_eq!(decoder.read_frame().unwrap().get_y_plane(), [2; 4]);
+        }
+    }
+
+    #[test]
+    fn slice_decoder_layout() {
+        let data = stream(&[b"FRAME\n", b"FRAME\n"]);
+        let mut decoder = SliceDecoder::new(&data).unwrap();
+        assert_eq!(decoder.frame_count().unwrap(), 2);
+        assert!(matches!(decoder.state.layout, FrameLayout::Fixed(2)));
+
+        let data = stream(&[b"FRAME Ixyz\n", b"FRAME\n"]);
+        let mut decoder = SliceDecoder::new(&data).unwrap();
+        assert_eq!(decoder.frame_count().unwrap(), 2);
+        assert!(matches!(decoder.state.layout, FrameLayout::Indexed(_)));
+        let frame = decoder.get_frame(0).unwrap();
+        assert_eq!(frame.get_raw_params(), Some(&b"Ixyz"[..]));
+        decoder.seek_to_frame(1).unwrap();
+        assert_eq!(decoder.read_frame().unwrap().get_y_plane(), [1; 4]);
+    }
+
+    #[cfg(target_pointer_width = "64")]
+    #[test]
+    fn frame_size_overflow() {
+        let data = b"YUV4MPEG2 W4294967296 H4294967297 C420\n";
+        assert!(matches!(
+            SliceDecoder::new(&data[..]),
+            Err(Error::OutOfMemory)
+        ));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn frame_index_round_trip() {
+        // Empty parameters must survive as well as missing ones.
+        let data = stream(&[b"FRAME\n", b"FRAME \n", b"FRAME Ixyz\n"]);
+        let mut decoder = decoder(&data);
+        let index = decoder.build_frame_index().unwrap();
+        let raw_params: Vec<_> = index
+            .entries()
+            .iter()
+            .map(|e| e.raw_params.clone())
+            .collect();
+        assert_eq!(raw_params, [None, Some(vec![]), Some(b"Ixyz".to_vec())]);
+
+        let mut sidecar = Vec::new();
+        index.write_to(&mut sidecar).unwrap();
+        let loaded = FrameIndex::read_from(&sidecar[..]).unwrap();
+        assert_eq!(loaded, index);
+
+        let mut decoder = self::decoder(&data);
+        decoder.set_frame_index(loaded).unwrap();
+        decoder.seek_to_frame(1).unwrap();
+        assert_eq!(
+            decoder.read_frame().unwrap().get_raw_params(),
+            Some(&[][..])
+        );
+
+        // Index of another stream is rejected.
+        let other = stream(&[b"FRAME\n"]);
+        let mut decoder = self::decoder(&other);
+        assert!(matches!(
+            decoder.set_frame_index(index.clone()),
+            Err(Error::BadInput)
+        ));
+
+        // Same layout but a different header is rejected as well.
+        let mut other = b"YUV4MPEG2 W2 H2 F30:1 C420\n".to_vec();
+        other.extend_from_slice(&data[HEADER.len()..]);
+        let mut decoder = self::decoder(&other);
+        assert!(matches!(
+            decoder.set_frame_index(index),
+            Err(Error::BadInput)
+        ));
+
+        // Truncated sidecar.
+        assert!(FrameIndex::read_from(&sidecar[..sidecar.len() - 1]).is_err());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn fixed_frame_index_with_truncated_tail() {
+        let mut data = stream(&[b"FRAME\n", b"FRAME\n"]);
+        data.extend_from_slice(b"FRA");
+        let mut decoder = decoder(&data);
+        let index = decoder.build_frame_index().unwrap();
+        assert_eq!(index.len(), 2);
+        assert_eq!(decoder.read_frame().unwrap().get_y_plane(), [0; 4]);
+
+        let mut decoder = self::decoder(&data);
+        decoder.set_frame_index(index).unwrap();
+        decoder.seek_to_frame(1).unwrap();
+        assert_eq!(decoder.read_frame().unwrap().get_y_plane(), [1; 4]);
+    }
+
+    #[test]
+    fn replace_color_description() {
+        let data = b"YUV4MPEG2 W2 H2 F25:1 XCOLORPRIMARIES=BT709 XCOLORTRC=BT709 C420\n";
+        let decoder = SliceDecoder::new(&data[..]).unwrap();
+        let color_description = decoder.get_color_description();
+        assert_eq!(color_description.primaries, Some(ColorPrimaries::Bt709));
+        assert_eq!(
+            color_description.transfer,
+            Some(TransferCharacteristics::Bt709)
+        );
+        assert_eq!(color_description.matrix, None);
+
+        let builder = EncoderBuilder::from_header(decoder.header()).with_color_description(
+            ColorDescription {
+                primaries: None,
+                transfer: Some(TransferCharacteristics::Bt709),
+                matrix: Some(MatrixCoefficients::Bt709),
+            },
+        );
+        assert_eq!(
+            builder.header_bytes(),
+            b"YUV4MPEG2 W2 H2 F25:1 XCOLORTRC=BT709 C420 XCOLORMATRIX=BT709\n"
+        );
+    }
+
+    #[test]
+    fn chroma_siting_follows_colorspace() {
+        let header = |data: &[u8]| SliceDecoder::new(data).unwrap().get_chroma_siting();
+        assert_eq!(
+            header(b"YUV4MPEG2 W2 H2 F25:1 XYSCSS=420MPEG2 C420\n"),
+            Some(ChromaSiting::Left)
+        );
+        assert_eq!(
+            header(b"YUV4MPEG2 W2 H2 F25:1 XYSCSS=420JPEG C420mpeg2\n"),
+            Some(ChromaSiting::Left)
+        );
+        assert_eq!(
+            header(b"YUV4MPEG2 W2 H2 F25:1 XYSCSS=420MPEG2 C444\n"),
+            None
+        );
+
+        let builder = EncoderBuilder::new(2, 2, Ratio::new(25, 1))
+            .with_colorspace(Colorspace::C420jpeg)
+            .with_colorspace(Colorspace::C420);
+        assert_eq!(builder.header_bytes(), b"YUV4MPEG2 W2 H2 F25:1 C420\n");
+
+        let data = b"YUV4MPEG2 W2 H2 F25:1 XYSCSS=420JPEG C420jpeg\n";
+        let decoder = SliceDecoder::new(&data[..]).unwrap();
+        let builder =
+            EncoderBuilder::from_header(decoder.header()).with_colorspace(Colorspace::C420);
+        assert_eq!(builder.header_bytes(), b"YUV4MPEG2 W2 H2 F25:1 C420\n");
+        let builder = EncoderBuilder::from_header(decoder.header())
+            .with_colorspace(Colorspace::C420)
+            .with_chroma_siting(ChromaSiting::Left);
+        assert_eq!(
+            builder.header_bytes(),
+            b"YUV4MPEG2 W2 H2 F25:1 C420mpeg2 XYSCSS=420MPEG2\n"
+        );
+    }
+
+    #[test]
+    fn parse_error_location() {
+        let parse_error = |data: &[u8]| match SliceDecoder::new(data) {
+            Err(Error::ParseError(err)) => err,
+            _ => panic!("expected parse error"),
+        };
+
+        let err = parse_error(b"YUV4MPEG2 W2 H2 F25:1 Cfoo\n");
+        assert_eq!(err.kind(), ParseErrorKind::UnknownColorspace);
+        assert_eq!((err.tag(), err.value()), (Some(b'C'), Some(&b"foo"[..])));
+        assert_eq!((err.offset(), err.frame()), (Some(22), None));
+        assert_eq!(
+            format!("{}", err),
+            "Bad C value `foo` at offset 22 (Unknown colorspace)"
+        );
+
+        let err = parse_error(b"YUV4MPEG2 H2 F25:1 C420\n");
+        assert_eq!(err.kind(), ParseErrorKind::MissingTag);
+        assert_eq!(format!("{}", err), "Missing W tag");
+
+        let err = parse_error(b"YUV4MPEG2 W2 H0 F25:1 C420\n");
+        assert_eq!(
+            format!("{}", err),
+            "Bad H value `0` at offset 13 (General parsing error)"
+        );
+
+        let mut data = stream(&[b"FRAME\n"]);
+        data.extend_from_slice(b"FRAMX\n");
+        let mut decoder = SliceDecoder::new(&data).unwrap();
+        decoder.read_frame().unwrap();
+        let err = match decoder.read_frame() {
+            Err(Error::ParseError(err)) => err,
+            _ => panic!("expected parse error"),
+        };
+        assert_eq!((err.offset(), err.frame()), (Some(39), Some(1)));
+        assert_eq!(
+            format!("{}", err),
+            "Error parsing y4m header in frame 1 at offset 39"
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn decoder_parse_error_location() {
+        let data = b"YUV4MPEG2 W2 H2 F25:1 Cfoo\n";
+        match Decoder::new(&data[..]) {
+            Err(Error::ParseError(err)) => assert_eq!(err.offset(), Some(22)),
+            _ => panic!("expected parse error"),
+        }
+
+        let mut data = stream(&[b"FRAME\n"]);
+        data.extend_from_slice(b"FRAMX\n");
+        let mut decoder = decoder(&data);
+        decoder.read_frame().unwrap();
+        match decoder.read_frame() {
+            Err(Error::ParseError(err)) => {
+                assert_eq!((err.offset(), err.frame()), (Some(39), Some(1)))
+            }
+            _ => panic!("expected parse error"),
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn frame_pool_reuse() {
+        let data = stream(&[b"FRAME\n", b"FRAME Ixyz\n"]);
+        let mut decoder = decoder(&data);
+        let mut pool = FramePool::new();
+        let mut frame = pool.get();
+        assert_eq!(frame.as_frame().get_y_plane(), []);
+        decoder.read_frame_buf(&mut frame).unwrap();
+        assert_eq!(frame.as_frame().get_y_plane(), [0; 4]);
+        let ptr = frame.as_frame().get_y_plane().as_ptr();
+        pool.recycle(frame);
+        assert_eq!(pool.len(), 1);
+
+        // Stale data isn't visible and the allocation is reused.
+        let mut frame = pool.get();
+        assert!(pool.is_empty());
+        assert_eq!(frame.as_frame().get_y_plane(), []);
+        assert_eq!(frame.as_frame().get_a_plane(), []);
+        decoder.read_frame_buf(&mut frame).unwrap();
+        assert_eq!(frame.as_frame().get_y_plane(), [1; 4]);
+        assert_eq!(frame.as_frame().get_u_plane(), [1]);
+        assert_eq!(frame.get_raw_params(), Some(&b"Ixyz"[..]));
+        assert_eq!(frame.as_frame().get_y_plane().as_ptr(), ptr);
+    }
+
+    #[test]
+    fn header_round_trip() {
+        let data = b"YUV4MPEG2 C420jpeg W2  H2 Ip XFOO=bar F30000:1001 A0:0 XYSCSS=420JPEG \n";
+        let decoder = SliceDecoder::new(&data[..]).unwrap();
+        let header = decoder.header();
+        assert_eq!(header.get_width(), 2);
+        let framerate = header.get_framerate();
+        assert_eq!((framerate.num, framerate.den), (30000, 1001));
+        assert_eq!(
+            EncoderBuilder::from_header(header).header_bytes(),
+            &data[..]
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn decoder_header_round_trip() {
+        let data = stream(&[b"FRAME\n"]);
+        let mut decoder = decoder(&data);
+        let mut out = Vec::new();
+        let mut encoder = EncoderBuilder::from_decoder(&decoder)
+            .write_header(&mut out)
+            .unwrap();
+        encoder.write_frame(&decoder.read_frame().unwrap()).unwrap();
+        assert_eq!(out, data);
+    }
+
+    #[test]
+    fn interlacing() {
+        for &(value, interlacing) in [
+            (&b"p"[..], Interlacing::Progressive),
+            (b"t", Interlacing::TopFieldFirst),
+            (b"b", Interlacing::BottomFieldFirst),
+            (b"m", Interlacing::Mixed),
+            (b"?", Interlacing::Unknown),
+        ]
+        .iter()
+        {
+            assert_eq!(Interlacing::parse(value).unwrap(), interlacing);
+            assert_eq!(format!("{}", interlacing).as_bytes(), value);
+        }
+        assert!(Interlacing::parse(b"x").is_err());
+
+        let decoder = SliceDecoder::new(b"YUV4MPEG2 W2 H2 F25:1 C420\n").unwrap();
+        assert_eq!(decoder.get_interlacing(), Interlacing::Progressive);
+        let decoder = SliceDecoder::new(b"YUV4MPEG2 W2 H2 F25:1 I? C420\n").unwrap();
+        assert_eq!(decoder.get_interlacing(), Interlacing::Unknown);
+        assert!(SliceDecoder::new(b"YUV4MPEG2 W2 H2 F25:1 Ix C420\n").is_err());
+
+        let builder = EncoderBuilder::new(2, 2, Ratio::new(25, 1))
+            .with_interlacing(Interlacing::BottomFieldFirst);
+        assert_eq!(builder.header_bytes(), b"YUV4MPEG2 W2 H2 F25:1 Ib C420\n");
+    }
+
+    #[test]
+    fn frame_params() {
+        let params = FrameParams::parse(b"Itp? XFOO=bar Zunknown").unwrap();
+        assert_eq!(
+            params.interlacing,
+            Some(FrameInterlacing {
+                presentation: FramePresentation::TopFieldFirst,
+                temporal: FrameSampling::Progressive,
+                subsampling: FrameSampling::Unknown,
+            })
+        );
+        assert_eq!(params.vendor_extensions[0].value(), b"FOO=bar");
+        assert_eq!(params.to_raw_params().unwrap(), b"Itp? XFOO=bar");
+
+        assert!(FrameParams::parse(b"Ixpp").is_err());
+        assert!(FrameParams::parse(b"I1p").is_err());
+        assert_eq!(FrameParams::parse(b"").unwrap(), FrameParams::new());
+        assert_eq!(FrameParams::new().to_raw_params(), None);
+
+        let data = stream(&[b"FRAME I3ii\n", b"FRAME\n"]);
+        let mut decoder = SliceDecoder::new(&data).unwrap();
+        let frame = decoder.read_frame().unwrap();
+        let params = frame.get_params().unwrap();
+        let interlacing = params.interlacing.unwrap();
+        assert_eq!(
+            interlacing.presentation,
+            FramePresentation::TripleProgressive
+        );
+        assert_eq!(interlacing.temporal, FrameSampling::Interlaced);
+        let frame = decoder.read_frame().unwrap();
+        assert_eq!(frame.get_params().unwrap(), FrameParams::new());
+
+        let frame = frame.with_params(&params);
+        assert_eq!(frame.get_raw_params(), Some(&b"I3ii"[..]));
+        let writer = EncoderBuilder::new(2, 2, Ratio::new(25, 1)).frame_writer();
+        assert_eq!(writer.frame_header(&frame).unwrap(), b"FRAME I3ii\n");
+    }
+
+    #[test]
+    fn high_bit_depth_colorspaces() {
+        for &(value, bit_depth, frame_size) in [
+            ("mono9", 9, 16),
+            ("mono16", 16, 16),
+            ("420p9", 9, 24),
+            ("420p14", 14, 24),
+            ("420p16", 16, 24),
+            ("422p14", 14, 32),
+            ("422p16", 16, 32),
+            ("444p9", 9, 48),
+            ("444p14", 14, 48),
+        ]
+        .iter()
+        {
+            let header = format!("YUV4MPEG2 W4 H2 F25:1 C{}\n", value);
+            let decoder = SliceDecoder::new(header.as_bytes()).unwrap();
+            assert_eq!(decoder.get_bit_depth(), bit_depth);
+            assert_eq!(decoder.get_bytes_per_sample(), 2);
+            assert_eq!(decoder.get_frame_size(), frame_size);
+            let builder = EncoderBuilder::new(4, 2, Ratio::new(25, 1))
+                .with_colorspace(decoder.get_colorspace());
+            assert_eq!(builder.header_bytes(), header.as_bytes());
+        }
+    }
+
+    #[test]
+    fn c411_plane_sizes() {
+        // Chroma width is rounded up.
+        assert_eq!(get_plane_sizes(6, 2, Colorspace::C411), (12, 4, 4, 0));
+        assert_eq!(get_plane_sizes(8, 2, Colorspace::C411), (16, 4, 4, 0));
+
+        let mut data = b"YUV4MPEG2 W6 H2 F25:1 C411\nFRAME\n".to_vec();
+        data.extend((0..20).map(|i| i as u8));
+        let mut decoder = SliceDecoder::new(&data).unwrap();
+        assert!(matches!(decoder.get_colorspace(), Colorspace::C411));
+        assert_eq!(decoder.get_frame_size(), 20);
+        let frame = decoder.read_frame().unwrap();
+        assert_eq!(frame.get_u_plane(), [12, 13, 14, 15]);
+        assert_eq!(frame.get_v_plane(), [16, 17, 18, 19]);
+
+        let builder =
+            EncoderBuilder::new(6, 2, Ratio::new(25, 1)).with_colorspace(Colorspace::C411);
+        assert_eq!(builder.header_bytes(), &data[..27]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn c444alpha() {
+        let mut out = Vec::new();
+        let mut encoder = EncoderBuilder::new(2, 1, Ratio::new(25, 1))
+            .with_colorspace(Colorspace::C444alpha)
+            .write_header(&mut out)
+            .unwrap();
+        let planes = [&[1, 1][..], &[2, 2], &[3, 3], &[4, 4]];
+        // Missing alpha plane is rejected.
+        let frame = Frame::new([planes[0], planes[1], planes[2]], None);
+        assert!(matches!(encoder.write_frame(&frame), Err(Error::BadInput)));
+        encoder
+            .write_frame(&Frame::new_with_alpha(planes, None))
+            .unwrap();
+        assert_eq!(
+            out,
+            b"YUV4MPEG2 W2 H1 F25:1 C444alpha\nFRAME\n\x01\x01\x02\x02\x03\x03\x04\x04"
+        );
+
+        let mut decoder = decoder(&out);
+        assert_eq!(decoder.get_frame_size(), 8);
+        let frame = decoder.read_frame().unwrap();
+        assert_eq!(frame.get_v_plane(), [3, 3]);
+        assert_eq!(frame.get_a_plane(), [4, 4]);
+
+        // Alpha plane is rejected for colorspaces without alpha.
+        let mut encoder = EncoderBuilder::new(2, 1, Ratio::new(25, 1))
+            .with_colorspace(Colorspace::C444)
+            .write_header(Vec::new())
+            .unwrap();
+        let frame = Frame::new_with_alpha(planes, None);
+        assert!(matches!(encoder.write_frame(&frame), Err(Error::BadInput)));
+    }
+
+    #[test]
+    fn color_range() {
+        let color_range = |data: &[u8]| SliceDecoder::new(data).unwrap().get_color_range();
+        assert_eq!(color_range(b"YUV4MPEG2 W2 H2 C420\n"), None);
+        assert_eq!(
+            color_range(b"YUV4MPEG2 W2 H2 XCOLORRANGE=FULL C420\n"),
+            Some(ColorRange::Full)
+        );
+        assert_eq!(
+            color_range(b"YUV4MPEG2 W2 H2 XCOLORRANGE=LIMITED C420\n"),
+            Some(ColorRange::Limited)
+        );
+        // Unknown values are ignored.
+        assert_eq!(color_range(b"YUV4MPEG2 W2 H2 XCOLORRANGE=foo C420\n"), None);
+
+        let builder =
+            EncoderBuilder::new(2, 2, Ratio::new(25, 1)).with_color_range(ColorRange::Full);
+        assert_eq!(
+            builder.header_bytes(),
+            b"YUV4MPEG2 W2 H2 F25:1 XCOLORRANGE=FULL C420\n"
+        );
+
+        // Preserved tag is replaced in place.
+        let data = b"YUV4MPEG2 W2 H2 XCOLORRANGE=FULL F25:1 C420\n";
+        let decoder = SliceDecoder::new(&data[..]).unwrap();
+        let builder =
+            EncoderBuilder::from_header(decoder.header()).with_color_range(ColorRange::Limited);
+        assert_eq!(
+            builder.header_bytes(),
+            b"YUV4MPEG2 W2 H2 XCOLORRANGE=LIMITED F25:1 C420\n"
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn frame_iterators() {
+        let data = stream(&[b"FRAME\n", b"FRAME Ixyz\n"]);
+        let mut decoder = decoder(&data);
+        let mut frames = decoder.frames();
+        assert_eq!(frames.next().unwrap().unwrap().get_y_plane(), [0; 4]);
+        assert_eq!(frames.next().unwrap().unwrap().get_y_plane(), [1; 4]);
+        assert!(frames.next().is_none());
+        assert!(frames.next().is_none());
+
+        let mut decoder = self::decoder(&data);
+        let frames: Vec<_> = decoder.owned_frames().map(|f| f.unwrap()).collect();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[1].as_frame().get_u_plane(), [1]);
+        assert_eq!(frames[1].get_raw_params(), Some(&b"Ixyz"[..]));
+
+        // Truncated frame is yielded once as an error.
+        let truncated = &data[..data.len() - 1];
+        let mut decoder = self::decoder(truncated);
+        let mut frames = decoder.frames();
+        assert!(frames.next().unwrap().is_ok());
+        assert!(matches!(
+            frames.next(),
+            Some(Err(Error::TruncatedFrame { .. }))
+        ));
+        assert!(frames.next().is_none());
+        let mut decoder = self::decoder(truncated);
+        let results: Vec<_> = decoder.owned_frames().collect();
+        assert_eq!(results.len(), 2);
+        assert!(results[1].is_err());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn truncated_input() {
+        assert!(matches!(
+            Decoder::new(&b"YUV4MPEG2 W2"[..]),
+            Err(Error::TruncatedHeader { read: 12 })
+        ));
+
+        let data = stream(&[b"FRAME\n"]);
+        let mut decoder = decoder(&data);
+        decoder.read_frame().unwrap();
+        assert!(matches!(decoder.read_frame(), Err(Error::EOF)));
+
+        let mut data = stream(&[b"FRAME\n"]);
+        data.extend_from_slice(b"FRA");
+        let mut decoder = self::decoder(&data);
+        decoder.read_frame().unwrap();
+        let err = decoder.read_frame().unwrap_err();
+        assert!(matches!(err, Error::TruncatedFrameHeader { read: 3 }));
+        assert_eq!(format!("{}", err), "Truncated frame header (3 bytes read)");
+
+        let data = stream(&[b"FRAME\n"]);
+        let mut decoder = self::decoder(&data[..data.len() - 2]);
+        let err = decoder.read_frame().unwrap_err();
+        assert!(matches!(
+            err,
+            Error::TruncatedFrame {
+                read: 4,
+                expected: 6
+            }
+        ));
+        assert_eq!(
+            format!("{}", err),
+            "Truncated frame data (4 of 6 bytes read)"
+        );
+    }
+
+    #[test]
+    fn slice_truncated_input() {
+        assert!(matches!(
+            SliceDecoder::new(b"YUV4MPEG2 W2"),
+            Err(Error::TruncatedHeader { read: 12 })
+        ));
+        let mut data = stream(&[b"FRAME\n"]);
+        data.extend_from_slice(b"FRAME\n\x01");
+        let mut decoder = SliceDecoder::new(&data).unwrap();
+        decoder.read_frame().unwrap();
+        assert!(matches!(
+            decoder.read_frame(),
+            Err(Error::TruncatedFrame {
+                read: 1,
+                expected: 6
+            })
+        ));
+        let data = stream(&[b"FRAME\n"]);
+        let mut decoder = SliceDecoder::new(&data).unwrap();
+        decoder.read_frame().unwrap();
+        assert!(matches!(decoder.read_frame(), Err(Error::EOF)));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn read_frame_into_buffer() {
+        let data = stream(&[b"FRAME\n", b"FRAME Ixyz\n"]);
+        let mut decoder = decoder(&data);
+        let mut buf = [0xff; 6];
+        assert!(matches!(
+            decoder.read_frame_into(&mut buf[..5]),
+            Err(Error::BadInput)
+        ));
+        assert_eq!(decoder.read_frame_into(&mut buf).unwrap(), None);
+        assert_eq!(buf, [0; 6]);
+        assert_eq!(
+            decoder.read_frame_into(&mut buf).unwrap(),
+            Some(b"Ixyz".to_vec())
+        );
+        assert_eq!(buf, [1; 6]);
+        assert!(matches!(decoder.read_frame_into(&mut buf), Err(Error::EOF)));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn buffered_reads() {
+        // Reader counting read calls.
+        struct CountingReader<'a>(&'a [u8], usize);
+        impl Read for CountingReader<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                self.1 += 1;
+                self.0.read(buf)
+            }
+        }
+
+        let data = stream(&[&b"FRAME Ixyz\n"[..]; 10]);
+        let mut decoder = Decoder::new(CountingReader(&data, 0)).unwrap();
+        while decoder.read_frame().is_ok() {}
+        assert!(decoder.into_inner().get_ref().1 <= 3);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn concatenated_streams() {
+        let mut data = stream(&[b"FRAME\n"]);
+        data.extend_from_slice(b"YUV4MPEG2 W1 H1 Cmono\nFRAME\n\x07");
+        let mut decoder = decoder(&data);
+        assert_eq!(decoder.read_frame().unwrap().get_y_plane(), [0; 4]);
+        // Read-ahead data of the next stream is kept in the reader.
+        let reader = decoder.into_inner();
+        assert!(!reader.buffer().is_empty());
+        let mut decoder = Decoder::from_buf_reader(reader, Limits::default()).unwrap();
+        assert_eq!(decoder.get_width(), 1);
+        assert_eq!(decoder.read_frame().unwrap().get_y_plane(), [7]);
+        assert!(matches!(decoder.read_frame(), Err(Error::EOF)));
+    }
+
+    // Encoding without IO, available without std.
+    #[test]
+    fn frame_writer() {
+        let builder = EncoderBuilder::new(2, 2, Ratio::new(25, 1));
+        let writer = builder.frame_writer();
+        let mut data = builder.header_bytes();
+        let frame = Frame::new([&[0; 4], &[0], &[0]], None);
+        writer.write_frame(&frame, &mut data).unwrap();
+        let frame = Frame::new([&[1; 4], &[1], &[1]], Some(b"Ixyz".to_vec()));
+        writer.write_frame(&frame, &mut data).unwrap();
+        assert_eq!(data, stream(&[b"FRAME\n", b"FRAME Ixyz\n"]));
+
+        let len = data.len();
+        let frame = Frame::new([&[1; 3], &[1], &[1]], None);
+        assert!(matches!(
+            writer.write_frame(&frame, &mut data),
+            Err(Error::BadInput)
+        ));
+        assert_eq!(data.len(), len);
+    }
+
+    #[test]
+    fn samples16_byte_order() {
+        let mut data = b"YUV4MPEG2 W2 H1 F25:1 C444p10\nFRAME\n".to_vec();
+        data.extend_from_slice(&[0x01, 0x02, 0xff, 0x03, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let mut decoder = SliceDecoder::new(&data).unwrap();
+        let frame = decoder.read_frame().unwrap();
+        let y = frame.get_y_plane_u16();
+        assert_eq!(y.len(), 2);
+        assert_eq!(y.clone().collect::<Vec<_>>(), [0x0201, 0x03ff]);
+        assert_eq!(y.rev().collect::<Vec<_>>(), [0x03ff, 0x0201]);
+        assert_eq!(frame.planes_u16()[3].len(), 0);
+
+        let frame = FrameBuf::from_u16([&[0x0201, 0x03ff], &[0; 2], &[0; 2]], None);
+        assert_eq!(frame.as_frame().get_y_plane(), [0x01, 0x02, 0xff, 0x03]);
+        assert_eq!(frame.data, &data[data.len() - 12..]);
+
+        let frame =
+            FrameBuf::from_u16_with_alpha([&[1], &[2], &[3], &[0x0100]], Some(b"Ixyz".to_vec()));
+        assert_eq!(frame.as_frame().get_a_plane(), [0x00, 0x01]);
+        assert_eq!(frame.get_raw_params(), Some(&b"Ixyz"[..]));
+
+        // Trailing odd byte is ignored.
+        assert_eq!(Samples16::new(&[1, 0, 2]).collect::<Vec<_>>(), [1]);
+    }
+
+    #[test]
+    fn plane_bounds() {
+        let data = [1, 2, 0, 3, 4, 0, 5, 6];
+        let plane = Plane::new(&data, 2, 3, 3, 1).unwrap();
+        assert_eq!(plane.row(2), Some(&[5, 6][..]));
+        assert_eq!(plane.row(3), None);
+        assert_eq!(plane.get(1, 1), Some(4));
+        assert_eq!(plane.get(2, 1), None);
+        assert_eq!(plane.rows().next_back(), Some(&[5, 6][..]));
+        let plane = Plane::new(&data, 1, 2, 3, 2).unwrap();
+        assert_eq!(plane.get(0, 1), Some(0x0403));
+
+        // Too short data, stride shorter than a row, bad sample size.
+        assert!(Plane::new(&data[..7], 2, 3, 3, 1).is_err());
+        assert!(Plane::new(&data, 2, 3, 1, 1).is_err());
+        assert!(Plane::new(&data, 1, 1, 4, 3).is_err());
+        assert!(Plane::new(&data, usize::MAX, 1, 1, 2).is_err());
+        assert!(Plane::new(&data, 1, usize::MAX, usize::MAX, 1).is_err());
+        assert_eq!(Plane::new(&[], 2, 0, 2, 1).unwrap().rows().len(), 0);
+    }
+
+    #[test]
+    fn write_padded_planes() {
+        let builder = EncoderBuilder::new(2, 2, Ratio::new(25, 1));
+        let writer = builder.frame_writer();
+        let y = [1, 1, 9, 9, 1, 1];
+        let u = [1, 9];
+        let planes = [
+            Plane::new(&y, 2, 2, 4, 1).unwrap(),
+            Plane::new(&u, 1, 1, 2, 1).unwrap(),
+            Plane::new(&u, 1, 1, 2, 1).unwrap(),
+        ];
+        let mut data = builder.header_bytes();
+        let frame = Frame::new([&[0; 4], &[0], &[0]], None);
+        writer.write_frame(&frame, &mut data).unwrap();
+        writer
+            .write_planes(&planes, Some(b"Ixyz"), &mut data)
+            .unwrap();
+        assert_eq!(data, stream(&[b"FRAME\n", b"FRAME Ixyz\n"]));
+
+        // Dimensions must match the stream.
+        let mut buf = Vec::new();
+        assert!(matches!(
+            writer.write_planes(&planes[..2], None, &mut buf),
+            Err(Error::BadInput)
+        ));
+        let wide = Plane::new(&y, 3, 1, 4, 1).unwrap();
+        assert!(matches!(
+            writer.write_planes(&[wide, planes[1], planes[2]], None, &mut buf),
+            Err(Error::BadInput)
+        ));
+        assert!(buf.is_empty());
+    }
+}
@@ -0,0 +1,105 @@
+//! Zero-copy decoder over memory-mapped files.
+
+use memmap2::Mmap;
+use std::fs::File;
+use std::path::Path;
+
+use crate::{
+    ChromaSiting, ColorDescription, ColorRange, Colorspace, Error, Frame, Header, Interlacing,
+    Ratio, SliceState,
+};
+
+/// YUV4MPEG2 decoder over a memory-mapped file.
+///
+/// Frames borrow directly from the mapped memory, so no frame data is copied
+/// and no frame buffer is allocated. Frames are accessed the same way as with
+/// `SliceDecoder`.
+pub struct MmapDecoder {
+    mmap: Mmap,
+    state: SliceState,
+}
+
+impl MmapDecoder {
+    /// Create a new decoder instance over the memory map.
+    pub fn new(mmap: Mmap) -> Result<MmapDecoder, Error> {
+        let state = SliceState::new(&mmap)?;
+        Ok(MmapDecoder { mmap, state })
+    }
+
+    /// Map the file and create a new decoder instance.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated while the decoder or any
+    /// of its frames are alive, see `memmap2::Mmap::map`.
+    pub unsafe fn open<P: AsRef<Path>>(path: P) -> Result<MmapDecoder, Error> {
+        let file = File::open(path)?;
+        MmapDecoder::new(Mmap::map(&file)?)
+    }
+
+    /// Iterate over frames. End of input is indicated by `Error::EOF`.
+    ///
+    /// `Error::EOF` is returned only if the file ends at a frame boundary,
+    /// otherwise one of the truncation errors is returned.
+    pub fn read_frame(&mut self) -> Result<Frame<'_>, Error> {
+        self.state.read_frame(&self.mmap)
+    }
+
+    /// Return the given frame without changing the position of `read_frame`.
+    /// `Error::BadInput` is returned if there is no such frame.
+    pub fn get_frame(&mut self, frame_index: usize) -> Result<Frame<'_>, Error> {
+        self.state.get_frame(&self.mmap, frame_index)
+    }
+
+    /// Return the number of complete frames in the file, see
+    /// `SliceDecoder::frame_count`.
+    pub fn frame_count(&mut self) -> Result<usize, Error> {
+        self.state.frame_count(&self.mmap)
+    }
+
+    /// Seek to the given frame, so the next `read_frame` call returns it.
+    /// Seeking to `frame_count` is allowed, reading then returns
+    /// `Error::EOF`. Seeking past that returns `Error::BadInput`.
+    pub fn seek_to_frame(&mut self, frame_index: usize) -> Result<(), Error> {
+        self.state.seek_to_frame(&self.mmap, frame_index)
+    }
+
+    header_getters!(state.header);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn frames() {
+        let path = std::env::temp_dir().join(format!("y4m-mmap-{}.y4m", std::process::id()));
+        let mut file = File::create(&path).unwrap();
+        file.write_all(b"YUV4MPEG2 W2 H1 F25:1 Cmono\n").unwrap();
+        for (i, frame_header) in [&b"FRAME\n"[..], b"FRAME Ixyz\n", b"FRAME\n"]
+            .iter()
+            .enumerate()
+        {
+            file.write_all(frame_header).unwrap();
+            file.write_all(&[i as u8; 2]).unwrap();
+        }
+        drop(file);
+
+        let mut decoder = unsafe { MmapDecoder::open(&path) }.unwrap();
+        assert_eq!(decoder.header().get_width(), 2);
+        assert!(matches!(decoder.get_colorspace(), Colorspace::Cmono));
+        assert_eq!(decoder.read_frame().unwrap().get_y_plane(), [0; 2]);
+        assert_eq!(decoder.frame_count().unwrap(), 3);
+        let frame = decoder.get_frame(1).unwrap();
+        assert_eq!(frame.get_y_plane(), [1; 2]);
+        assert_eq!(frame.get_raw_params(), Some(&b"Ixyz"[..]));
+        assert!(matches!(decoder.get_frame(3), Err(Error::BadInput)));
+        decoder.seek_to_frame(2).unwrap();
+        assert_eq!(decoder.read_frame().unwrap().get_y_plane(), [2; 2]);
+        assert!(matches!(decoder.read_frame(), Err(Error::EOF)));
+
+        drop(decoder);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,372 @@
+//! Sans-IO push parser.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::{
+    copy_until, parse_frame_header_line, parse_header_line, Error, Header, Limits, MAX_PARAMS_SIZE,
+    TERMINATOR,
+};
+
+/// Event emitted by the `Parser`.
+#[derive(Debug, Clone)]
+pub enum Event<'a> {
+    /// File header was parsed. Emitted once at the start of the stream.
+    Header(Header),
+    /// Frame header was parsed, contains frame raw parameters if any.
+    FrameHeader(Option<Vec<u8>>),
+    /// Chunk of plane data borrowed from the pushed input.
+    PlaneData {
+        /// Plane index: 0 for Y, 1 for U, 2 for V and 3 for A.
+        plane: usize,
+        /// Offset of the chunk within the plane.
+        offset: usize,
+        /// Plane bytes.
+        data: &'a [u8],
+    },
+    /// All planes of the current frame were emitted.
+    FrameEnd,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum State {
+    Header,
+    FrameHeader,
+    // Number of frame data bytes emitted so far.
+    FrameData(usize),
+}
+
+/// Push-based YUV4MPEG2 parser which doesn't do any IO.
+///
+/// Input can be fed in arbitrary chunks, frame data is never copied or
+/// buffered: plane data events borrow the pushed input. Only the header
+/// lines are buffered if they are split between chunks. Any error is fatal,
+/// the parser shouldn't be used after that.
+pub struct Parser {
+    limits: Limits,
+    line_buf: Vec<u8>,
+    // Length of the partially received header line.
+    collected: usize,
+    header: Option<Header>,
+    state: State,
+    // Stream offset and index of the next frame.
+    offset: u64,
+    frame_index: usize,
+}
+
+impl Parser {
+    /// Create a new parser instance.
+    pub fn new() -> Parser {
+        Parser::new_with_limits(Limits::default())
+    }
+
+    /// Create a new parser instance with custom limits.
+    pub fn new_with_limits(limits: Limits) -> Parser {
+        Parser {
+            limits,
+            line_buf: vec![0; MAX_PARAMS_SIZE],
+            collected: 0,
+            header: None,
+            state: State::Header,
+            offset: 0,
+            frame_index: 0,
+        }
+    }
+
+    /// Return the file header once it's parsed.
+    pub fn header(&self) -> Option<&Header> {
+        self.header.as_ref()
+    }
+
+    /// Push the next chunk of input and iterate over the resulting events.
+    ///
+    /// The whole chunk is consumed once the iterator is exhausted, if it's
+    /// dropped earlier, the rest is available with `Events::remaining`.
+    pub fn push<'p, 'a>(&'p mut self, input: &'a [u8]) -> Events<'p, 'a> {
+        Events {
+            parser: self,
+            remaining: input,
+            done: false,
+        }
+    }
+
+    /// Parse the beginning of the input. Return the number of consumed bytes
+    /// and an event if any.
+    ///
+    /// No event and zero consumed bytes are returned only for empty input.
+    pub fn parse<'a>(&mut self, input: &'a [u8]) -> Result<(usize, Option<Event<'a>>), Error> {
+        match self.state {
+            State::Header | State::FrameHeader if input.is_empty() => Ok((0, None)),
+            State::Header | State::FrameHeader => self.parse_line(input),
+            State::FrameData(read) => Ok(self.parse_frame_data(input, read)),
+        }
+    }
+
+    /// Check that the stream ended at a frame boundary.
+    ///
+    /// `Error::EOF` is returned if the file header is missing, otherwise one of
+    /// the truncation errors is returned for incomplete stream.
+    pub fn finish(&self) -> Result<(), Error> {
+        match self.state {
+            State::Header if self.collected == 0 => Err(Error::EOF),
+            State::Header => Err(Error::TruncatedHeader {
+                read: self.collected,
+            }),
+            State::FrameHeader if self.collected == 0 => Ok(()),
+            State::FrameHeader => Err(Error::TruncatedFrameHeader {
+                read: self.collected,
+            }),
+            State::FrameData(read) => {
+                let expected = self.frame_size();
+                if read == expected {
+                    Ok(())
+                } else {
+                    Err(Error::TruncatedFrame { read, expected })
+                }
+            }
+        }
+    }
+
+    fn parse_line<'a>(&mut self, input: &'a [u8]) -> Result<(usize, Option<Event<'a>>), Error> {
+        let res = copy_until(input, TERMINATOR, &mut self.line_buf, &mut self.collected);
+        let (consumed, line) = match res {
+            Ok((consumed, false)) => return Ok((consumed, None)),
+            Ok((consumed, true)) => (consumed, Ok(self.collected)),
+            Err(err) => (0, Err(err)),
+        };
+        self.collected = 0;
+        let event = match self.state {
+            State::Header => {
+                let (header, end_params_pos) =
+                    parse_header_line(line, &self.line_buf, self.limits)?;
+                self.offset = end_params_pos as u64 + 1;
+                self.header = Some(header.clone());
+                Event::Header(header)
+            }
+            _ => {
+                let (raw_params, end_params_pos) =
+                    parse_frame_header_line(line, &self.line_buf, self.offset, self.frame_index)?;
+                self.offset += end_params_pos as u64 + 1;
+                Event::FrameHeader(raw_params)
+            }
+        };
+        self.state = match self.state {
+            State::Header => State::FrameHeader,
+            _ => State::FrameData(0),
+        };
+        Ok((consumed, Some(event)))
+    }
+
+    fn parse_frame_data<'a>(&mut self, input: &'a [u8], read: usize) -> (usize, Option<Event<'a>>) {
+        let header = self.header.as_ref().expect("header is parsed");
+        if read == header.frame_size {
+            self.offset += header.frame_size as u64;
+            self.frame_index += 1;
+            self.state = State::FrameHeader;
+            return (0, Some(Event::FrameEnd));
+        }
+        if input.is_empty() {
+            return (0, None);
+        }
+        // Find the plane containing the current position, empty planes are
+        // skipped.
+        let mut start = 0;
+        for (plane, &plane_len) in header.plane_lens.iter().enumerate() {
+            if read < start + plane_len {
+                let len = input.len().min(start + plane_len - read);
+                self.state = State::FrameData(read + len);
+                let event = Event::PlaneData {
+                    plane,
+                    offset: read - start,
+                    data: &input[..len],
+                };
+                return (len, Some(event));
+            }
+            start += plane_len;
+        }
+        unreachable!()
+    }
+
+    fn frame_size(&self) -> usize {
+        self.header.as_ref().map_or(0, |header| header.frame_size)
+    }
+}
+
+impl Default for Parser {
+    fn default() -> Parser {
+        Parser::new()
+    }
+}
+
+/// Iterator over events of the pushed input.
+pub struct Events<'p, 'a> {
+    parser: &'p mut Parser,
+    remaining: &'a [u8],
+    done: bool,
+}
+
+impl<'a> Events<'_, 'a> {
+    /// Return the input not consumed yet.
+    pub fn remaining(&self) -> &'a [u8] {
+        self.remaining
+    }
+}
+
+impl<'a> Iterator for Events<'_, 'a> {
+    type Item = Result<Event<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        loop {
+            match self.parser.parse(self.remaining) {
+                Ok((consumed, event)) => {
+                    self.remaining = &self.remaining[consumed..];
+                    if let Some(event) = event {
+                        return Some(Ok(event));
+                    }
+                    if self.remaining.is_empty() {
+                        return None;
+                    }
+                }
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::borrow::ToOwned;
+    use alloc::format;
+
+    const HEADER: &[u8] = b"YUV4MPEG2 W2 H2 F25:1 C420\n";
+    const FRAME_SIZE: usize = 6;
+
+    // Two frames, the second one with parameters.
+    fn stream() -> Vec<u8> {
+        let mut data = HEADER.to_vec();
+        data.extend_from_slice(b"FRAME\n");
+        data.extend_from_slice(&[0, 1, 2, 3, 4, 5]);
+        data.extend_from_slice(b"FRAME Ixyz\n");
+        data.extend_from_slice(&[6, 7, 8, 9, 10, 11]);
+        data
+    }
+
+    type Frames = Vec<(Option<Vec<u8>>, Vec<u8>)>;
+
+    // Push the input in chunks and collect frame parameters and data.
+    fn parse(parser: &mut Parser, data: &[u8], chunk_size: usize) -> Result<Frames, Error> {
+        let plane_starts = [0, 4, 5];
+        let mut frames: Frames = Vec::new();
+        for chunk in data.chunks(chunk_size) {
+            for event in parser.push(chunk) {
+                match event? {
+                    Event::Header(header) => assert_eq!(header.get_frame_size(), FRAME_SIZE),
+                    Event::FrameHeader(raw_params) => frames.push((raw_params, Vec::new())),
+                    Event::PlaneData {
+                        plane,
+                        offset,
+                        data,
+                    } => {
+                        let frame = &mut frames.last_mut().unwrap().1;
+                        assert_eq!(frame.len(), plane_starts[plane] + offset);
+                        frame.extend_from_slice(data);
+                    }
+                    Event::FrameEnd => assert_eq!(frames.last().unwrap().1.len(), FRAME_SIZE),
+                }
+            }
+        }
+        Ok(frames)
+    }
+
+    #[test]
+    fn chunked_input() {
+        let data = stream();
+        let expected = vec![
+            (None, vec![0, 1, 2, 3, 4, 5]),
+            (Some(b"Ixyz".to_vec()), vec![6, 7, 8, 9, 10, 11]),
+        ];
+        for &chunk_size in [1, 2, 3, 5, 7, 13, data.len()].iter() {
+            let mut parser = Parser::new();
+            let frames = parse(&mut parser, &data, chunk_size).unwrap();
+            assert_eq!(frames, expected, "chunk size {}", chunk_size);
+            parser.finish().unwrap();
+        }
+    }
+
+    #[test]
+    fn events_remaining() {
+        let data = stream();
+        let mut parser = Parser::new();
+        let mut events = parser.push(&data);
+        assert!(matches!(events.next(), Some(Ok(Event::Header(_)))));
+        assert_eq!(events.remaining(), &data[HEADER.len()..]);
+    }
+
+    #[test]
+    fn truncated_input() {
+        let data = stream();
+        // Start and end of the file header, frame headers and frame data.
+        let segments = [
+            (0, HEADER.len()),
+            (HEADER.len(), HEADER.len() + 6),
+            (HEADER.len() + 6, HEADER.len() + 12),
+            (HEADER.len() + 12, HEADER.len() + 23),
+            (HEADER.len() + 23, HEADER.len() + 29),
+        ];
+        assert_eq!(segments[4].1, data.len());
+        for len in 0..=data.len() {
+            // Segment being read when the input ends.
+            let segment = segments
+                .iter()
+                .position(|&(start, end)| start <= len && len < end);
+            let expected = match segment {
+                _ if len == 0 => "Err(EOF)".to_owned(),
+                Some(0) => format!("Err(TruncatedHeader {{ read: {} }})", len),
+                Some(i) if i % 2 == 1 && len > segments[i].0 => format!(
+                    "Err(TruncatedFrameHeader {{ read: {} }})",
+                    len - segments[i].0
+                ),
+                Some(i) if i % 2 == 0 => format!(
+                    "Err(TruncatedFrame {{ read: {}, expected: {} }})",
+                    len - segments[i].0,
+                    FRAME_SIZE
+                ),
+                _ => "Ok(())".to_owned(),
+            };
+            for &chunk_size in [1, 4, data.len()].iter() {
+                let mut parser = Parser::new();
+                parse(&mut parser, &data[..len], chunk_size).unwrap();
+                assert_eq!(format!("{:?}", parser.finish()), expected, "length {}", len);
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_input() {
+        let mut parser = Parser::new();
+        let err = parse(&mut parser, b"YUV4MPEG W2 H2\n", 1).unwrap_err();
+        assert!(matches!(err, Error::ParseError(_)));
+
+        let mut data = HEADER.to_vec();
+        data.extend_from_slice(b"FRAMEX\n");
+        let mut parser = Parser::new();
+        let err = parse(&mut parser, &data, 3).unwrap_err();
+        assert!(matches!(err, Error::ParseError(_)));
+    }
+
+    #[test]
+    fn limits() {
+        let mut parser = Parser::new_with_limits(Limits {
+            bytes: FRAME_SIZE - 1,
+        });
+        let err = parse(&mut parser, &stream(), 1).unwrap_err();
+        assert!(matches!(err, Error::OutOfMemory));
+    }
+}